@@ -0,0 +1,289 @@
+use std::f32::consts::{PI, TAU};
+use std::path::Path;
+
+const MIN_PITCH: f32 = 20.0; // Hz
+const MAX_PITCH: f32 = 5000.0;
+const PITCH_WINDOW: usize = 2048;
+const YIN_THRESHOLD: f32 = 0.15;
+const ANALYSIS_PERIODS: f32 = 16.0;
+pub(crate) const MAX_CYCLE_LENGTH: usize = 8192; // longer files are several cycles or a whole table
+
+// enough of the middle of a sample for the pitch and the harmonics, at up to 192 kHz
+pub(crate) const ANALYSIS_LENGTH: usize = (ANALYSIS_PERIODS * 192000.0 / MIN_PITCH) as usize;
+
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Harmonic {
+    pub(crate) amplitude: f32,
+    pub(crate) phase: f32, // cycles, relative to the fundamental
+}
+
+// mono mixdown of at most `max_frames` from the middle of a wav file, and its sample rate
+pub(crate) fn read_wav(path: &Path, max_frames: usize) -> Result<(Vec<f32>, f32), hound::Error> {
+    let mut reader = hound::WavReader::open(path)?;
+    let spec = reader.spec();
+    let frames = reader.duration() as usize;
+    let length = frames.min(max_frames);
+    reader.seek(((frames - length) / 2) as u32)?;
+    let channels = spec.channels.max(1) as usize;
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .take(length * channels)
+            .collect::<Result<_, _>>()?,
+        hound::SampleFormat::Int => {
+            let scale = 1.0 / (1u64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .take(length * channels)
+                .map(|s| s.map(|s| s as f32 * scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    let samples = interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+    Ok((samples, spec.sample_rate as f32))
+}
+
+// YIN on a window from the middle of the sample, skipping the attack
+pub(crate) fn detect_pitch(samples: &[f32], sample_rate: f32) -> Option<f32> {
+    let min_lag = (sample_rate / MAX_PITCH) as usize;
+    let max_lag = (sample_rate / MIN_PITCH) as usize;
+    let needed = PITCH_WINDOW + max_lag;
+    if samples.len() < needed {
+        return None;
+    }
+    let start = (samples.len() - needed) / 2;
+    let frame = &samples[start..start + needed];
+
+    let mut difference = vec![0.0f32; max_lag + 1];
+    for (lag, d) in difference.iter_mut().enumerate().skip(1) {
+        *d = (0..PITCH_WINDOW)
+            .map(|i| {
+                let delta = frame[i] - frame[i + lag];
+                delta * delta
+            })
+            .sum();
+    }
+
+    // cumulative mean normalized difference
+    let mut running_sum = 0.0;
+    let mut normalized = vec![1.0f32; max_lag + 1];
+    for (lag, (n, d)) in normalized.iter_mut().zip(&difference).enumerate().skip(1) {
+        running_sum += d;
+        if running_sum > 0.0 {
+            *n = d * lag as f32 / running_sum;
+        }
+    }
+
+    let mut lag = min_lag.max(2);
+    while lag < max_lag {
+        if normalized[lag] < YIN_THRESHOLD {
+            while lag + 1 < max_lag && normalized[lag + 1] < normalized[lag] {
+                lag += 1;
+            }
+            break;
+        }
+        lag += 1;
+    }
+    if lag >= max_lag {
+        return None;
+    }
+
+    // parabolic interpolation around the minimum
+    let (a, b, c) = (normalized[lag - 1], normalized[lag], normalized[lag + 1]);
+    let denominator = a - 2.0 * b + c;
+    let offset = if denominator.abs() > f32::EPSILON {
+        0.5 * (a - c) / denominator
+    } else {
+        0.0
+    };
+    Some(sample_rate / (lag as f32 + offset))
+}
+
+// amplitude and phase of the first `count` harmonics of `pitch`, evaluated at the exact harmonic
+// frequencies over a Hann window from the middle of the sample
+pub(crate) fn measure_harmonics(
+    samples: &[f32],
+    sample_rate: f32,
+    pitch: f32,
+    count: usize,
+) -> Vec<Harmonic> {
+    let length = ((ANALYSIS_PERIODS * sample_rate / pitch) as usize).min(samples.len());
+    let start = (samples.len() - length) / 2;
+    let frame = &samples[start..start + length];
+    let window: Vec<f32> = (0..length)
+        .map(|i| 0.5 - 0.5 * (TAU * i as f32 / length as f32).cos())
+        .collect();
+    let window_sum: f32 = window.iter().sum();
+
+    let mut harmonics: Vec<Harmonic> = (1..=count)
+        .map(|k| {
+            let freq = k as f32 * pitch;
+            if freq >= sample_rate / 2.0 {
+                return Harmonic::default();
+            }
+            harmonic(dft_bin(frame, &window, freq / sample_rate), window_sum)
+        })
+        .collect();
+
+    // shift in time so the fundamental starts at phase zero
+    let fundamental_phase = harmonics.first().map_or(0.0, |h| h.phase);
+    for (k, harmonic) in harmonics.iter_mut().enumerate() {
+        harmonic.phase = (harmonic.phase - (k + 1) as f32 * fundamental_phase).rem_euclid(1.0);
+    }
+    harmonics
+}
+
+// spectrum of a file holding exactly one cycle, no window needed since it's periodic
+pub(crate) fn cycle_harmonics(cycle: &[f32], count: usize) -> Vec<Harmonic> {
+    let length = cycle.len();
+    let window = vec![1.0; length];
+    (1..=count)
+        .map(|k| {
+            if 2 * k >= length {
+                return Harmonic::default();
+            }
+            harmonic(
+                dft_bin(cycle, &window, k as f32 / length as f32),
+                length as f32,
+            )
+        })
+        .collect()
+}
+
+// linear interpolation around the loop, so the end joins the start
+pub(crate) fn resample_cycle(cycle: &[f32], length: usize) -> Vec<f32> {
+    (0..length)
+        .map(|i| {
+            let position = i as f32 * cycle.len() as f32 / length as f32;
+            let index = position as usize;
+            let frac = position - index as f32;
+            let a = cycle[index];
+            let b = cycle[(index + 1) % cycle.len()];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+// a sine of amplitude A and phase θ shows up as A / 2 * e^(i(θ - π / 2)) times the window sum
+fn harmonic((re, im): (f32, f32), window_sum: f32) -> Harmonic {
+    Harmonic {
+        amplitude: 2.0 * (re * re + im * im).sqrt() / window_sum,
+        phase: ((im.atan2(re) + PI / 2.0) / TAU).rem_euclid(1.0),
+    }
+}
+
+// single DFT bin at `freq` cycles per sample, f64 keeps the angle accurate over long windows
+fn dft_bin(frame: &[f32], window: &[f32], freq: f32) -> (f32, f32) {
+    let omega = std::f64::consts::TAU * freq as f64;
+    let (mut re, mut im) = (0.0f64, 0.0f64);
+    for (n, (x, w)) in frame.iter().zip(window).enumerate() {
+        let angle = omega * n as f64;
+        let value = (x * w) as f64;
+        re += value * angle.cos();
+        im -= value * angle.sin();
+    }
+    (re as f32, im as f32)
+}
+
+// scales the amplitudes so the loudest harmonic sits at `peak`
+pub(crate) fn normalize(harmonics: &mut [Harmonic], peak: f32) {
+    let max = harmonics.iter().fold(0.0f32, |max, h| max.max(h.amplitude));
+    if max > 0.0 {
+        for harmonic in harmonics.iter_mut() {
+            harmonic.amplitude *= peak / max;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    // amplitude and phase in cycles of each harmonic of `pitch`
+    fn synthesize(pitch: f32, harmonics: &[(f32, f32)], length: usize) -> Vec<f32> {
+        (0..length)
+            .map(|n| {
+                let t = n as f32 / SAMPLE_RATE;
+                harmonics
+                    .iter()
+                    .enumerate()
+                    .map(|(k, (amplitude, phase))| {
+                        amplitude * (TAU * ((k + 1) as f32 * pitch * t + phase)).sin()
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn detects_the_fundamental() {
+        let samples = synthesize(220.0, &[(1.0, 0.0), (0.5, 0.25), (0.25, 0.5)], 48000);
+        let pitch = detect_pitch(&samples, SAMPLE_RATE).unwrap();
+        assert!((pitch - 220.0).abs() < 0.5, "{pitch}");
+    }
+
+    #[test]
+    fn finds_no_pitch_in_silence() {
+        assert!(detect_pitch(&[0.0; 48000], SAMPLE_RATE).is_none());
+        assert!(detect_pitch(&[0.0; 100], SAMPLE_RATE).is_none());
+    }
+
+    #[test]
+    fn measures_amplitudes_and_phases() {
+        let expected = [(1.0, 0.0), (0.5, 0.25), (0.25, 0.5), (0.0, 0.0)];
+        let samples = synthesize(220.0, &expected, 48000);
+        let harmonics = measure_harmonics(&samples, SAMPLE_RATE, 220.0, 4);
+        for (harmonic, (amplitude, phase)) in harmonics.iter().zip(expected) {
+            assert!((harmonic.amplitude - amplitude).abs() < 0.02);
+            if amplitude > 0.0 {
+                let error = (harmonic.phase - phase + 0.5).rem_euclid(1.0) - 0.5;
+                assert!(error.abs() < 0.01, "{} {phase}", harmonic.phase);
+            }
+        }
+    }
+
+    #[test]
+    fn skips_harmonics_above_nyquist() {
+        let samples = synthesize(10000.0, &[(1.0, 0.0)], 4800);
+        let harmonics = measure_harmonics(&samples, SAMPLE_RATE, 10000.0, 3);
+        assert!(harmonics[0].amplitude > 0.9);
+        assert_eq!(harmonics[2].amplitude, 0.0);
+    }
+
+    #[test]
+    fn reads_the_spectrum_of_a_cycle() {
+        let cycle: Vec<f32> = (0..256)
+            .map(|n| {
+                let p = n as f32 / 256.0;
+                (TAU * p).sin() + 0.5 * (TAU * (3.0 * p + 0.25)).sin()
+            })
+            .collect();
+        let harmonics = cycle_harmonics(&cycle, 4);
+        assert!((harmonics[0].amplitude - 1.0).abs() < 1e-3);
+        assert!(harmonics[1].amplitude < 1e-3);
+        assert!((harmonics[2].amplitude - 0.5).abs() < 1e-3);
+        assert!((harmonics[2].phase - 0.25).abs() < 1e-3);
+    }
+
+    #[test]
+    fn resamples_a_cycle_around_the_loop() {
+        let resampled = resample_cycle(&[0.0, 1.0, 0.0, -1.0], 8);
+        assert_eq!(resampled, [0.0, 0.5, 1.0, 0.5, 0.0, -0.5, -1.0, -0.5]);
+    }
+
+    #[test]
+    fn normalizes_to_the_peak() {
+        let mut harmonics = [0.5, 0.25].map(|amplitude| Harmonic {
+            amplitude,
+            phase: 0.0,
+        });
+        normalize(&mut harmonics, 1.0);
+        assert_eq!(harmonics[0].amplitude, 1.0);
+        assert_eq!(harmonics[1].amplitude, 0.5);
+    }
+}
@@ -1,11 +1,13 @@
-use nih_plug::prelude::Editor;
+use nih_plug::prelude::{Editor, Param};
 use nih_plug_vizia::vizia::prelude::*;
 
 use nih_plug_vizia::widgets::*;
 use nih_plug_vizia::{create_vizia_editor, ViziaState, ViziaTheming};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
-use crate::FuririParams;
+use crate::analysis::{self, Harmonic};
+use crate::{wavetable, FuririParams, MAX_FM_SLOTS, MAX_LFOS, MAX_MOD_SLOTS, MAX_UNDERTONES};
 
 mod adsr;
 use adsr::Adsr;
@@ -16,12 +18,187 @@ use waveform::Waveform;
 #[derive(Lens)]
 struct Data {
     params: Arc<FuririParams>,
+    page: Page,
+    status: String,
 }
 
-impl Model for Data {}
+#[derive(Clone, Copy, PartialEq)]
+enum Page {
+    Main,
+    Partials,
+    Voice,
+    Fm,
+    Lfo,
+    Matrix,
+}
+
+// the model is called `Data` as well, so the trait needs its full path here
+impl nih_plug_vizia::vizia::prelude::Data for Page {
+    fn same(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+const PAGES: [(Page, &str); 6] = [
+    (Page::Main, "Main"),
+    (Page::Partials, "Partials"),
+    (Page::Voice, "Voice"),
+    (Page::Fm, "FM"),
+    (Page::Lfo, "LFO"),
+    (Page::Matrix, "Matrix"),
+];
+
+enum EditorEvent {
+    SetPage(Page),
+    Resynthesize,
+    ApplyHarmonics(Vec<Harmonic>, Vec<f32>, String), // the spectrum, the preview cycle and status
+    ImportWavetable,
+    ExportCycle,
+    ExportTiltSweep,
+    ExportMorphSweep,
+    CopySpectrum,
+    SetStatus(String),
+}
+
+impl Model for Data {
+    fn event(&mut self, cx: &mut EventContext, event: &mut Event) {
+        event.map(|editor_event, _| match editor_event {
+            EditorEvent::SetPage(page) => self.page = *page,
+            EditorEvent::Resynthesize => {
+                let count = self.params.partial_count.value().count();
+                pick_wav(cx, move |path| resynthesize(&path, count));
+            }
+            EditorEvent::ApplyHarmonics(harmonics, cycle, status) => {
+                self.apply_harmonics(cx, harmonics);
+                *self.params.imported_cycle.write().unwrap() = cycle.clone();
+                self.status = status.clone();
+            }
+            EditorEvent::ImportWavetable => {
+                let count = self.params.partial_count.value().count();
+                pick_wav(cx, move |path| import_wavetable(&path, count));
+            }
+            EditorEvent::ExportCycle => export(cx, wavetable::single_cycle(&self.params)),
+            EditorEvent::ExportTiltSweep => export(cx, wavetable::tilt_sweep(&self.params)),
+            EditorEvent::ExportMorphSweep => export(cx, wavetable::morph_sweep(&self.params)),
+            EditorEvent::CopySpectrum => self.copy_spectrum(cx),
+            EditorEvent::SetStatus(status) => self.status = status.clone(),
+        });
+    }
+}
+
+impl Data {
+    // spectrum a into b, as a starting point for the morph target
+    fn copy_spectrum(&self, cx: &mut EventContext) {
+        for overtone in self.params.overtones.iter() {
+            set_parameter(cx, &overtone.amplitude_b, overtone.amplitude.value());
+            set_parameter(cx, &overtone.phase_b, overtone.phase.value());
+        }
+    }
+
+    // the measured spectrum replaces the sound, so everything else that shapes it goes back to
+    // neutral, the overtones past the measured ones and the macros the notes apply as they play,
+    // both spectra get it so the morph leaves it alone
+    fn apply_harmonics(&self, cx: &mut EventContext, harmonics: &[Harmonic]) {
+        for (i, overtone) in self.params.overtones.iter().enumerate() {
+            let harmonic = harmonics.get(i).copied().unwrap_or_default();
+            for (amplitude, phase) in [
+                (&overtone.amplitude, &overtone.phase),
+                (&overtone.amplitude_b, &overtone.phase_b),
+            ] {
+                set_parameter(cx, amplitude, harmonic.amplitude.min(2.0));
+                set_parameter(cx, phase, harmonic.phase * 360.0);
+            }
+            for param in [&overtone.ratio, &overtone.decay_scale, &overtone.pan] {
+                set_parameter(cx, param, param.default_plain_value());
+            }
+        }
+        for undertone in self.params.undertones.iter() {
+            set_parameter(cx, &undertone.amplitude, 0.0);
+        }
+        for param in [
+            &self.params.tilt,
+            &self.params.odd_even,
+            &self.params.inharmonicity,
+        ] {
+            set_parameter(cx, param, param.default_plain_value());
+        }
+    }
+}
+
+// the native dialog blocks until it's closed, so it runs on its own thread, which loads the
+// chosen file as well and sends back the event `load` makes of it
+fn pick_wav(cx: &mut EventContext, load: impl FnOnce(PathBuf) -> EditorEvent + Send + 'static) {
+    cx.spawn(move |cx| {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .pick_file()
+        {
+            let _ = cx.emit(load(path));
+        }
+    });
+}
+
+// on the dialog's thread, only the measured spectrum goes back to the editor
+fn resynthesize(path: &Path, count: usize) -> EditorEvent {
+    let (samples, sample_rate) = match analysis::read_wav(path, analysis::ANALYSIS_LENGTH) {
+        Ok(sample) => sample,
+        Err(err) => return EditorEvent::SetStatus(format!("Could not read sample: {err}")),
+    };
+    let Some(pitch) = analysis::detect_pitch(&samples, sample_rate) else {
+        return EditorEvent::SetStatus(String::from("No pitch found"));
+    };
+    let mut harmonics = analysis::measure_harmonics(&samples, sample_rate, pitch, count);
+    analysis::normalize(&mut harmonics, 1.0);
+    let status = format!("Resynthesized at {pitch:.1} Hz");
+    EditorEvent::ApplyHarmonics(harmonics, Vec::new(), status)
+}
+
+// on the dialog's thread as well, one sample past the longest cycle is enough to turn down a
+// whole table without reading it
+fn import_wavetable(path: &Path, count: usize) -> EditorEvent {
+    let status = match analysis::read_wav(path, analysis::MAX_CYCLE_LENGTH + 1) {
+        Err(err) => format!("Could not read wavetable: {err}"),
+        Ok((cycle, _)) if cycle.len() < 4 => String::from("Wavetable is too short"),
+        Ok((cycle, _)) if cycle.len() > analysis::MAX_CYCLE_LENGTH => {
+            String::from("Wavetable is longer than a single cycle")
+        }
+        Ok((cycle, _)) => {
+            let harmonics = analysis::cycle_harmonics(&cycle, count);
+            let status = format!("Imported {} sample cycle", cycle.len());
+            // the preview is saved with the plugin state, so it's kept at a fixed size
+            let preview = analysis::resample_cycle(&cycle, wavetable::CYCLE_LENGTH);
+            return EditorEvent::ApplyHarmonics(harmonics, preview, status);
+        }
+    };
+    EditorEvent::SetStatus(status)
+}
+
+// the frames are rendered right away, the dialog and the write happen on their own thread
+fn export(cx: &mut EventContext, frames: Vec<Vec<f32>>) {
+    cx.spawn(move |cx| {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .set_file_name("furiri.wav")
+            .save_file()
+        else {
+            return;
+        };
+        let status = match wavetable::write_wav(&path, &frames) {
+            Err(err) => format!("Could not write wavetable: {err}"),
+            Ok(()) => format!("Exported {} frame(s)", frames.len()),
+        };
+        let _ = cx.emit(EditorEvent::SetStatus(status));
+    });
+}
+
+fn set_parameter<P: Param>(cx: &mut EventContext, param: &P, value: P::Plain) {
+    cx.emit(ParamEvent::BeginSetParameter(param).upcast());
+    cx.emit(ParamEvent::SetParameter(param, value).upcast());
+    cx.emit(ParamEvent::EndSetParameter(param).upcast());
+}
 
 pub(crate) fn default_state() -> Arc<ViziaState> {
-    ViziaState::new(|| (800, 400))
+    ViziaState::new(|| (860, 480))
 }
 
 pub(crate) fn create(
@@ -33,55 +210,375 @@ pub(crate) fn create(
 
         Data {
             params: params.clone(),
+            page: Page::Main,
+            status: String::new(),
         }
         .build(cx);
 
-        HStack::new(cx, |cx| {
-            VStack::new(cx, |cx| {
-                Label::new(cx, "Furiri.")
-                    .font_size(20.0)
-                    .font_weight(FontWeightKeyword::Bold);
-                Label::new(cx, "Envelope").height(Pixels(20.0));
-                ParamSlider::new(cx, Data::params, |params| &params.envelope.attack);
-                ParamSlider::new(cx, Data::params, |params| &params.envelope.decay);
-                ParamSlider::new(cx, Data::params, |params| &params.envelope.sustain);
-                ParamSlider::new(cx, Data::params, |params| &params.envelope.release);
-                Label::new(cx, "Gain").height(Pixels(20.0));
-                ParamSlider::new(cx, Data::params, |params| &params.gain);
-                Adsr::new(cx, Data::params).height(Pixels(50.0));
+        VStack::new(cx, |cx| {
+            Binding::new(cx, Data::page, |cx, page| {
+                let page = page.get(cx);
+                HStack::new(cx, |cx| {
+                    Label::new(cx, "Furiri.")
+                        .font_size(20.0)
+                        .font_weight(FontWeightKeyword::Bold);
+                    for (target, name) in PAGES {
+                        let tab = Label::new(cx, name)
+                            .height(Pixels(20.0))
+                            .top(Pixels(5.0))
+                            .on_press(move |cx| cx.emit(EditorEvent::SetPage(target)));
+                        if target == page {
+                            tab.font_weight(FontWeightKeyword::Bold);
+                        }
+                    }
+                })
+                .col_between(Pixels(20.0))
+                .height(Auto);
+            });
+
+            Binding::new(cx, Data::page, |cx, page| match page.get(cx) {
+                Page::Main => main_page(cx),
+                Page::Partials => partials_page(cx),
+                Page::Voice => voice_page(cx),
+                Page::Fm => fm_page(cx),
+                Page::Lfo => lfo_page(cx),
+                Page::Matrix => matrix_page(cx),
+            });
+        })
+        .row_between(Pixels(10.0))
+        .top(Pixels(10.0))
+        .left(Pixels(20.0));
+    })
+}
+
+fn main_page(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Envelope").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.delay);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.attack);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.hold);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.decay);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.sustain);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.release);
+            Adsr::new(cx, Data::params).height(Pixels(50.0));
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Envelope Shape").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.attack_curve);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.decay_curve);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.release_curve);
+            ParamSlider::new(cx, Data::params, |params| &params.envelope.sync);
+            Binding::new(
+                cx,
+                Data::params.map(|params| params.envelope.sync.value()),
+                |cx, sync| {
+                    if sync.get(cx) {
+                        VStack::new(cx, |cx| {
+                            ParamSlider::new(cx, Data::params, |params| {
+                                &params.envelope.delay_division
+                            });
+                            ParamSlider::new(cx, Data::params, |params| {
+                                &params.envelope.attack_division
+                            });
+                            ParamSlider::new(cx, Data::params, |params| {
+                                &params.envelope.hold_division
+                            });
+                            ParamSlider::new(cx, Data::params, |params| {
+                                &params.envelope.decay_division
+                            });
+                            ParamSlider::new(cx, Data::params, |params| {
+                                &params.envelope.release_division
+                            });
+                        })
+                        .row_between(Pixels(10.0))
+                        .height(Auto);
+                    }
+                },
+            );
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Base Freq & Midi Note").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.basepitch);
+            ParamSlider::new(cx, Data::params, |params| &params.basenote);
+            Label::new(cx, "Tuning").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.tuning);
+            Label::new(cx, "Gain").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.gain);
+            Waveform::new(cx, Data::params).height(Pixels(100.0));
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Timbre").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.inharmonicity);
+            ParamSlider::new(cx, Data::params, |params| &params.tilt);
+            ParamSlider::new(cx, Data::params, |params| &params.odd_even);
+            Label::new(cx, "Stereo").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.width);
+            ParamSlider::new(cx, Data::params, |params| &params.key_pan);
+            ParamSlider::new(cx, Data::params, |params| &params.random_pan);
+        })
+        .row_between(Pixels(10.0));
+    })
+    .col_between(Pixels(30.0));
+}
+
+fn partials_page(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Overtones").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.partial_count);
+            Waveform::new(cx, Data::params).height(Pixels(100.0));
+            Label::new(cx, "Load Sample...")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::Resynthesize));
+            Label::new(cx, "Load Wavetable...")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::ImportWavetable));
+            Label::new(cx, "Export Cycle...")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::ExportCycle));
+            Label::new(cx, "Export Tilt Sweep...")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::ExportTiltSweep));
+            Label::new(cx, "Export Morph Sweep...")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::ExportMorphSweep));
+            Label::new(cx, "Copy A to B")
+                .height(Pixels(20.0))
+                .on_press(|cx| cx.emit(EditorEvent::CopySpectrum));
+            Label::new(cx, Data::status).height(Pixels(20.0));
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            HStack::new(cx, |cx| {
+                Label::new(cx, "Amplitude A").width(Pixels(120.0));
+                Label::new(cx, "Amplitude B").width(Pixels(120.0));
+                for name in ["Phase A", "Phase B", "Ratio", "Decay", "Pan"] {
+                    Label::new(cx, name).width(Pixels(60.0));
+                }
             })
-            .row_between(Pixels(10.0));
+            .col_between(Pixels(5.0))
+            .height(Pixels(20.0));
+            ScrollView::new(cx, 0.0, 0.0, false, true, |cx| {
+                Binding::new(
+                    cx,
+                    Data::params.map(|params| params.partial_count.value().count()),
+                    |cx, count| {
+                        VStack::new(cx, |cx| {
+                            for i in 0..count.get(cx) {
+                                partial_row(cx, i);
+                            }
+                            Label::new(cx, "Undertones").height(Pixels(20.0));
+                            for i in 0..MAX_UNDERTONES {
+                                undertone_row(cx, i);
+                            }
+                        })
+                        .row_between(Pixels(10.0))
+                        .height(Auto);
+                    },
+                );
+            })
+            .height(Pixels(380.0));
+        })
+        .row_between(Pixels(10.0));
+    })
+    .col_between(Pixels(30.0));
+}
+
+fn partial_row(cx: &mut Context, i: usize) {
+    HStack::new(cx, |cx| {
+        ParamSlider::new(cx, Data::params, move |params| {
+            &params.overtones[i].amplitude
+        })
+        .width(Pixels(120.0));
+        ParamSlider::new(cx, Data::params, move |params| {
+            &params.overtones[i].amplitude_b
+        })
+        .width(Pixels(120.0));
+        ParamSlider::new(cx, Data::params, move |params| &params.overtones[i].phase)
+            .width(Pixels(60.0));
+        ParamSlider::new(cx, Data::params, move |params| &params.overtones[i].phase_b)
+            .width(Pixels(60.0));
+        ParamSlider::new(cx, Data::params, move |params| &params.overtones[i].ratio)
+            .width(Pixels(60.0));
+        ParamSlider::new(cx, Data::params, move |params| {
+            &params.overtones[i].decay_scale
+        })
+        .width(Pixels(60.0));
+        ParamSlider::new(cx, Data::params, move |params| &params.overtones[i].pan)
+            .width(Pixels(60.0));
+    })
+    .col_between(Pixels(5.0))
+    .height(Auto);
+}
+
+fn undertone_row(cx: &mut Context, i: usize) {
+    HStack::new(cx, |cx| {
+        ParamSlider::new(cx, Data::params, move |params| {
+            &params.undertones[i].amplitude
+        })
+        .width(Pixels(120.0));
+        Label::new(cx, format!("1/{}", i + 2)).width(Pixels(60.0));
+    })
+    .col_between(Pixels(5.0))
+    .height(Auto);
+}
+
+fn voice_page(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Voices").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.polyphony);
+            ParamSlider::new(cx, Data::params, |params| &params.steal_policy);
+            ParamSlider::new(cx, Data::params, |params| &params.voice_mode);
+            ParamSlider::new(cx, Data::params, |params| &params.note_priority);
+            Label::new(cx, "Glide").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.glide_time);
+            ParamSlider::new(cx, Data::params, |params| &params.glide_curve);
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Unison").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.unison.voices);
+            ParamSlider::new(cx, Data::params, |params| &params.unison.detune);
+            ParamSlider::new(cx, Data::params, |params| &params.unison.spread);
+            ParamSlider::new(cx, Data::params, |params| &params.unison.random_phase);
+            Label::new(cx, "Phase").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.phase_mode);
+            ParamSlider::new(cx, Data::params, |params| &params.partial_random_phase);
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Noise").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.noise.level);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.center);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.width);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.delay);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.attack);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.decay);
+            ParamSlider::new(cx, Data::params, |params| &params.noise.sustain);
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Morph").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.morph.amount);
+            ParamSlider::new(cx, Data::params, |params| &params.morph.velocity);
+            ParamSlider::new(cx, Data::params, |params| &params.morph.mod_wheel);
+            ParamSlider::new(cx, Data::params, |params| &params.morph.envelope);
+            ParamSlider::new(cx, Data::params, |params| &params.morph.time);
+            Label::new(cx, "Velocity").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.velocity.curve);
+            ParamSlider::new(cx, Data::params, |params| &params.velocity.shape);
+            ParamSlider::new(cx, Data::params, |params| &params.velocity.brightness);
+        })
+        .row_between(Pixels(10.0));
+    })
+    .col_between(Pixels(30.0));
+}
+
+fn fm_page(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        VStack::new(cx, |cx| {
+            HStack::new(cx, |cx| {
+                for name in ["Source", "Target", "Index"] {
+                    Label::new(cx, name).width(Pixels(120.0));
+                }
+            })
+            .col_between(Pixels(5.0))
+            .height(Pixels(20.0));
+            for i in 0..MAX_FM_SLOTS {
+                HStack::new(cx, |cx| {
+                    ParamSlider::new(cx, Data::params, move |params| &params.fm_slots[i].source)
+                        .width(Pixels(120.0));
+                    ParamSlider::new(cx, Data::params, move |params| &params.fm_slots[i].target)
+                        .width(Pixels(120.0));
+                    ParamSlider::new(cx, Data::params, move |params| &params.fm_slots[i].index)
+                        .width(Pixels(120.0));
+                })
+                .col_between(Pixels(5.0))
+                .height(Auto);
+            }
+        })
+        .row_between(Pixels(10.0));
+
+        VStack::new(cx, |cx| {
+            Label::new(cx, "Index Envelope").height(Pixels(20.0));
+            ParamSlider::new(cx, Data::params, |params| &params.fm.delay);
+            ParamSlider::new(cx, Data::params, |params| &params.fm.attack);
+            ParamSlider::new(cx, Data::params, |params| &params.fm.decay);
+            ParamSlider::new(cx, Data::params, |params| &params.fm.sustain);
+            ParamSlider::new(cx, Data::params, |params| &params.fm.velocity);
+        })
+        .row_between(Pixels(10.0));
+    })
+    .col_between(Pixels(50.0));
+}
 
+fn lfo_page(cx: &mut Context) {
+    HStack::new(cx, |cx| {
+        for i in 0..MAX_LFOS {
             VStack::new(cx, |cx| {
-                Label::new(cx, "Base Freq & Midi Note")
-                    .height(Pixels(20.0))
-                    .top(Pixels(20.0));
-                ParamSlider::new(cx, Data::params, |params| &params.basepitch);
-                ParamSlider::new(cx, Data::params, |params| &params.basenote);
-                Label::new(cx, "Tuning")
-                    .height(Pixels(20.0));
-                ParamSlider::new(cx, Data::params, |params| &params.tuning);
-                Waveform::new(cx, Data::params).height(Pixels(100.0));
+                Label::new(cx, format!("LFO {}", i + 1)).height(Pixels(20.0));
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].shape);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].rate);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].sync);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].beats);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].phase);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].mode);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].fade);
             })
-            .row_between(Pixels(10.0))
-            .top(Pixels(25.0));
+            .row_between(Pixels(10.0));
 
             VStack::new(cx, |cx| {
-                Label::new(cx, "Overtones");
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone1);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone2);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone3);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone4);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone5);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone6);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone7);
-                ParamSlider::new(cx, Data::params, |params| &params.overtones.overtone8);
+                Label::new(cx, "Destinations").height(Pixels(20.0));
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].pitch);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].gain);
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].overtone);
+                ParamSlider::new(cx, Data::params, move |params| {
+                    &params.lfos[i].overtone_depth
+                });
+                ParamSlider::new(cx, Data::params, move |params| &params.lfos[i].envelope);
             })
-            .row_between(Pixels(10.0))
-            .top(Pixels(20.0));
+            .row_between(Pixels(10.0));
+        }
+    })
+    .col_between(Pixels(30.0));
+}
+
+// the number picks the partial, fm slot or lfo for the targets that have several
+fn matrix_page(cx: &mut Context) {
+    VStack::new(cx, |cx| {
+        HStack::new(cx, |cx| {
+            for name in ["Source", "Target", "Number", "Depth"] {
+                Label::new(cx, name).width(Pixels(120.0));
+            }
         })
-        .top(Pixels(10.0))
-        .col_between(Pixels(50.0))
-        .left(Pixels(20.0));
+        .col_between(Pixels(5.0))
+        .height(Pixels(20.0));
+        for i in 0..MAX_MOD_SLOTS {
+            HStack::new(cx, |cx| {
+                ParamSlider::new(cx, Data::params, move |params| &params.mod_slots[i].source)
+                    .width(Pixels(120.0));
+                ParamSlider::new(cx, Data::params, move |params| &params.mod_slots[i].target)
+                    .width(Pixels(120.0));
+                ParamSlider::new(cx, Data::params, move |params| &params.mod_slots[i].number)
+                    .width(Pixels(120.0));
+                ParamSlider::new(cx, Data::params, move |params| &params.mod_slots[i].depth)
+                    .width(Pixels(120.0));
+            })
+            .col_between(Pixels(5.0))
+            .height(Auto);
+        }
     })
+    .row_between(Pixels(10.0));
 }
@@ -1,7 +1,9 @@
 use nih_plug_vizia::vizia::{prelude::*, vg};
 use std::sync::Arc;
 
-use crate::FuririParams;
+use crate::{current, envelope_level, Envelope, FuririParams};
+
+const MIN_SECONDS: f32 = 0.5;
 
 pub struct Adsr<V>
 where
@@ -33,26 +35,27 @@ where
             &{
                 let mut path = vg::Path::new();
                 let binding = self.data.get(cx);
+                // synced times are drawn at 120 bpm
+                let envelope = Envelope::new(&binding.envelope, Some(120.0), current);
 
-                let attack = binding.envelope.attack.value() / 1000.0;
-                let decay = binding.envelope.decay.value() / 1000.0;
-                let sustain = binding.envelope.sustain.value();
-                let release = binding.envelope.release.value() / 1000.0;
-
+                // the key is held a bit past the decay, then released
+                const STEP_SIZE: f32 = 2.0;
+                let held = envelope.delay + envelope.attack + envelope.hold + envelope.decay;
+                let gate = (held * 1.25).max(MIN_SECONDS);
+                let seconds = gate + envelope.release.max(MIN_SECONDS);
+                let release_level = envelope_level(false, gate, &envelope, 0.0, 1.0);
                 path.move_to(bounds.x, bounds.y + bounds.h);
-                path.line_to(bounds.x + bounds.w * attack / 8.0, bounds.y);
-                path.line_to(
-                    bounds.x + bounds.w * (attack + decay) / 8.0,
-                    bounds.y + bounds.h * (1.0 - sustain),
-                );
-                path.line_to(
-                    bounds.x + bounds.w * 3. / 8.,
-                    bounds.y + bounds.h * (1.0 - sustain),
-                );
-                path.line_to(
-                    bounds.x + bounds.w * (3. + release) / 8.,
-                    bounds.y + bounds.h,
-                );
+                let mut x = bounds.x;
+                while x < bounds.x + bounds.w {
+                    let time = seconds * (x - bounds.x) / bounds.w;
+                    let level = if time < gate {
+                        envelope_level(false, time, &envelope, 0.0, 1.0)
+                    } else {
+                        envelope_level(true, time - gate, &envelope, release_level, 1.0)
+                    };
+                    path.line_to(x, bounds.y + bounds.h * (1.0 - level));
+                    x += STEP_SIZE;
+                }
 
                 path
             },
@@ -1,7 +1,10 @@
 use nih_plug_vizia::vizia::{prelude::*, vg};
 use std::sync::Arc;
 
-use crate::FuririParams;
+use crate::{FuririParams, Partial};
+
+const INHARMONIC_PERIODS: f32 = 4.0;
+const MAX_PERIODS: usize = 12; // undertones repeat after several periods of the fundamental
 
 pub struct Waveform<V>
 where
@@ -29,31 +32,54 @@ where
 
     fn draw(&self, cx: &mut DrawContext, canvas: &mut Canvas) {
         let bounds = cx.bounds();
+        let binding = self.data.get(cx);
+
+        let morph = binding.morph.amount.value();
+        let partials: Vec<Partial> = binding.partials().map(|p| p.morphed(morph)).collect();
+        // show the full period of the audible partials, or several fundamental periods when the
+        // spectrum is inharmonic so the drift is visible
+        let periods = (1..=MAX_PERIODS)
+            .find(|&n| {
+                partials.iter().filter(|p| p.amplitude != 0.0).all(|p| {
+                    let cycles = p.ratio * n as f32;
+                    (cycles - cycles.round()).abs() < 1e-4
+                })
+            })
+            .map_or(INHARMONIC_PERIODS, |n| n as f32);
+        const STEP_SIZE: f32 = 2.0;
+
+        // the imported cycle underneath, so the reconstruction can be compared against it
+        let cycle = binding.imported_cycle.read().unwrap();
+        if periods == 1.0 && !cycle.is_empty() {
+            let mut path = vg::Path::new();
+            path.move_to(bounds.x, bounds.y + (1.0 - cycle[0] / 8.0) * bounds.h / 2.0);
+            let mut x = bounds.x;
+            while x < bounds.x + bounds.w {
+                let s = cycle[((x - bounds.x) / bounds.w * cycle.len() as f32) as usize];
+                path.line_to(x, bounds.y + (1.0 - s / 8.0) * bounds.h / 2.0);
+                x += STEP_SIZE;
+            }
+            canvas.stroke_path(
+                &path,
+                &vg::Paint::color(vg::Color::rgba(128, 128, 128, 160)).with_line_width(1.0),
+            );
+        }
+
         let mut max: f32 = 0.0;
         canvas.stroke_path(
             &{
                 let mut path = vg::Path::new();
-                let binding = self.data.get(cx);
-
-                let overtones: [f32; 8] = [
-                    binding.overtones.overtone1.value(),
-                    binding.overtones.overtone2.value(),
-                    binding.overtones.overtone3.value(),
-                    binding.overtones.overtone4.value(),
-                    binding.overtones.overtone5.value(),
-                    binding.overtones.overtone6.value(),
-                    binding.overtones.overtone7.value(),
-                    binding.overtones.overtone8.value(),
-                ];
-                const STEP_SIZE: f32 = 2.0;
                 path.move_to(bounds.x, bounds.y + bounds.h / 2.0);
                 let mut x = bounds.x;
                 while x < bounds.x + bounds.w {
-                    let p = (x - bounds.x) / bounds.w;
-                    let s = overtones
+                    let p = periods * (x - bounds.x) / bounds.w;
+                    let s = partials
                         .iter()
-                        .enumerate()
-                        .map(|(i, v)| v * (std::f32::consts::TAU * (1.0 + i as f32) * p).sin())
+                        .map(|partial| {
+                            partial.amplitude
+                                * (std::f32::consts::TAU * (partial.ratio * p + partial.phase))
+                                    .sin()
+                        })
                         .sum::<f32>();
                     max = max.max(s.abs());
                     path.line_to(x, bounds.y + (1.0 - s / 8.0) * bounds.h / 2.0);
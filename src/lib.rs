@@ -1,13 +1,25 @@
-use nih_plug::{prelude::*, util::db_to_gain_fast};
-use std::sync::Arc;
+use nih_plug::{
+    prelude::*,
+    util::{db_to_gain, db_to_gain_fast},
+};
+use std::f32::consts::TAU;
+use std::sync::{Arc, RwLock};
 
 mod editor;
 use nih_plug_vizia::ViziaState;
 
+mod analysis;
+mod wavetable;
+
+mod oscillator;
+use oscillator::{BandNoise, Lfo, Rng, SineTable};
+
 #[derive(Params)]
 pub struct FuririParams {
     #[persist = "editor-state"]
     editor_state: Arc<ViziaState>,
+    #[persist = "imported-cycle"]
+    imported_cycle: RwLock<Vec<f32>>, // original of the last imported wavetable, for the preview
     #[id = "basepitch"]
     basepitch: FloatParam,
     #[id = "basenote"]
@@ -16,10 +28,58 @@ pub struct FuririParams {
     tuning: EnumParam<Tuning>,
     #[id = "gain"]
     gain: FloatParam,
+    #[id = "partials"]
+    partial_count: EnumParam<PartialCount>,
+    #[id = "inharmonicity"]
+    inharmonicity: FloatParam,
+    #[id = "tilt"]
+    tilt: FloatParam,
+    #[id = "odd_even"]
+    odd_even: FloatParam,
+    #[id = "width"]
+    width: FloatParam,
+    #[id = "key_pan"]
+    key_pan: FloatParam,
+    #[id = "random_pan"]
+    random_pan: FloatParam,
+    #[id = "voice_mode"]
+    voice_mode: EnumParam<VoiceMode>,
+    #[id = "note_priority"]
+    note_priority: EnumParam<NotePriority>,
+    #[id = "glide"]
+    glide_time: FloatParam,
+    #[id = "glide_curve"]
+    glide_curve: FloatParam,
+    #[id = "polyphony"]
+    polyphony: IntParam, // counts every unison copy
+    #[id = "steal_policy"]
+    steal_policy: EnumParam<StealPolicy>,
+    #[id = "phase_mode"]
+    phase_mode: EnumParam<PhaseMode>,
+    #[id = "partial_random_phase"]
+    partial_random_phase: BoolParam,
     #[nested]
-    overtones: OvertoneParams,
+    overtones: Overtones,
+    #[nested(array, group = "Undertone")]
+    undertones: [UndertoneParams; MAX_UNDERTONES],
     #[nested]
     envelope: EnvelopeParams,
+    #[nested]
+    unison: UnisonParams,
+    #[nested]
+    noise: NoiseParams,
+    #[nested]
+    velocity: VelocityParams,
+    #[nested]
+    morph: MorphParams,
+    #[nested(array, group = "FM")]
+    fm_slots: [FmSlotParams; MAX_FM_SLOTS],
+    #[nested]
+    fm: FmParams,
+    #[nested(array, group = "LFO")]
+    lfos: [LfoParams; MAX_LFOS],
+    #[nested(array, group = "Mod")]
+    mod_slots: [ModSlotParams; MAX_MOD_SLOTS],
 }
 
 #[derive(Enum, PartialEq)]
@@ -29,42 +89,610 @@ enum Tuning {
     Pythagorean,
 }
 
+#[derive(Enum, PartialEq)]
+enum VoiceMode {
+    Poly,
+    Mono,   // retriggers the envelopes on every new key
+    Legato, // keeps the envelopes running while keys overlap
+}
+
+#[derive(Enum, PartialEq)]
+enum NotePriority {
+    Last,
+    Low,
+    High,
+}
+
+#[derive(Enum, PartialEq, Clone, Copy)]
+enum StealPolicy {
+    Oldest,
+    Quietest,
+    #[name = "Released First"]
+    Released,
+    #[name = "Same Note First"]
+    SameNote,
+}
+
+#[derive(Enum, PartialEq)]
+enum PhaseMode {
+    Reset,
+    Free, // follows a global clock, as if the oscillators never stopped
+    Random,
+}
+
+#[derive(Enum, PartialEq, Clone, Copy, Default)]
+enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    #[name = "S&H"]
+    SampleHold,
+    #[name = "Smooth Random"]
+    SmoothRandom,
+}
+
+#[derive(Enum, PartialEq)]
+enum LfoMode {
+    Global, // one oscillator shared by all notes
+    #[name = "Per Voice"]
+    Voice, // restarts with every note
+}
+
+#[derive(Enum, PartialEq, Clone, Copy, Default)]
+enum ModSource {
+    #[default]
+    Velocity,
+    Key, // -1 to 1 over the keyboard
+    #[name = "Mod Wheel"]
+    ModWheel,
+    Aftertouch, // polyphonic or channel pressure, whichever is higher
+    #[name = "Pitch Bend"]
+    PitchBend,
+    Envelope,
+    #[name = "Noise Envelope"]
+    NoiseEnvelope,
+    #[name = "FM Envelope"]
+    FmEnvelope,
+    #[name = "LFO 1"]
+    Lfo1,
+    #[name = "LFO 2"]
+    Lfo2,
+}
+
+// every continuous parameter and the discrete ones that can change while a note plays, the slot's
+// number picks the partial, fm slot or lfo where there are several, tuning, voice allocation,
+// phase settings, syncs, modes and the matrix itself stay put
+#[derive(Enum, PartialEq, Clone, Copy, Default)]
+enum ModTarget {
+    #[default]
+    #[name = "Base Pitch"]
+    BasePitch,
+    Gain,
+    Inharmonicity,
+    Tilt,
+    #[name = "Odd/Even"]
+    OddEven,
+    Width,
+    #[name = "Key Pan"]
+    KeyPan,
+    #[name = "Random Pan"]
+    RandomPan,
+    Glide,
+    #[name = "Glide Curve"]
+    GlideCurve,
+    Detune,
+    Spread,
+    Overtone,
+    #[name = "Overtone B"]
+    OvertoneB,
+    Phase,
+    #[name = "Phase B"]
+    PhaseB,
+    Ratio,
+    #[name = "Decay Scale"]
+    DecayScale,
+    Pan,
+    Undertone,
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    // release and the curves move the noise and fm envelopes as well, they have none of their own
+    Release,
+    #[name = "Attack Curve"]
+    AttackCurve,
+    #[name = "Decay Curve"]
+    DecayCurve,
+    #[name = "Release Curve"]
+    ReleaseCurve,
+    #[name = "Noise"]
+    NoiseLevel,
+    #[name = "Noise Center"]
+    NoiseCenter,
+    #[name = "Noise Width"]
+    NoiseWidth,
+    #[name = "Noise Attack"]
+    NoiseAttack,
+    #[name = "Noise Decay"]
+    NoiseDecay,
+    #[name = "Noise Sustain"]
+    NoiseSustain,
+    #[name = "Velocity Shape"]
+    VelocityShape,
+    #[name = "Velocity Brightness"]
+    VelocityBrightness,
+    Morph,
+    #[name = "Morph Velocity"]
+    MorphVelocity,
+    #[name = "Morph Mod Wheel"]
+    MorphModWheel,
+    #[name = "Morph Envelope"]
+    MorphEnvelope,
+    #[name = "Morph Time"]
+    MorphTime,
+    #[name = "FM Index"]
+    FmIndex,
+    #[name = "FM Attack"]
+    FmAttack,
+    #[name = "FM Decay"]
+    FmDecay,
+    #[name = "FM Sustain"]
+    FmSustain,
+    #[name = "FM Velocity"]
+    FmVelocity,
+    #[name = "LFO Rate"]
+    LfoRate,
+    #[name = "LFO Beats"]
+    LfoBeats,
+    #[name = "LFO Phase"]
+    LfoPhase,
+    #[name = "LFO Fade"]
+    LfoFade,
+    #[name = "LFO Pitch"]
+    LfoPitch,
+    #[name = "LFO Gain"]
+    LfoGain,
+    #[name = "LFO Overtone Depth"]
+    LfoOvertoneDepth,
+    #[name = "LFO Envelope"]
+    LfoEnvelope,
+    #[name = "Partial Count"]
+    PartialCount,
+    #[name = "Unison Voices"]
+    UnisonVoices, // read once as the note starts
+    #[name = "Velocity Curve"]
+    VelocityCurve,
+    #[name = "FM Source"]
+    FmSource,
+    #[name = "FM Target"]
+    FmTarget,
+    #[name = "LFO Shape"]
+    LfoShape,
+    #[name = "LFO Overtone"]
+    LfoOvertone,
+}
+
+// note lengths for the tempo synced envelope times, a beat being a quarter note
+#[derive(Enum, PartialEq)]
+enum Division {
+    Off, // the stage keeps its millisecond time
+    #[name = "1/64"]
+    SixtyFourth,
+    #[name = "1/32"]
+    ThirtySecond,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/2"]
+    Half,
+    #[name = "1 Bar"]
+    Bar,
+    #[name = "2 Bars"]
+    TwoBars,
+    #[name = "4 Bars"]
+    FourBars,
+    #[name = "8 Bars"]
+    EightBars,
+}
+
+#[derive(Enum, PartialEq)]
+enum VelocityCurve {
+    Linear,
+    Exponential,
+    Logarithmic,
+    Custom, // power curve set by the shape
+}
+
+#[derive(Enum, PartialEq)]
+enum PartialCount {
+    #[name = "8"]
+    Eight,
+    #[name = "16"]
+    Sixteen,
+    #[name = "32"]
+    ThirtyTwo,
+    #[name = "64"]
+    SixtyFour,
+}
+
+impl PartialCount {
+    fn count(&self) -> usize {
+        match self {
+            PartialCount::Eight => 8,
+            PartialCount::Sixteen => 16,
+            PartialCount::ThirtyTwo => 32,
+            PartialCount::SixtyFour => MAX_PARTIALS,
+        }
+    }
+}
+
 #[derive(Params)]
 struct EnvelopeParams {
+    #[id = "delay"]
+    delay: FloatParam,
     #[id = "attack"]
     attack: FloatParam,
+    #[id = "hold"]
+    hold: FloatParam,
     #[id = "decay"]
     decay: FloatParam,
     #[id = "sustain"]
     sustain: FloatParam,
     #[id = "release"]
     release: FloatParam,
+    #[id = "envelope_sync"]
+    sync: BoolParam, // the divisions replace the times
+    #[id = "delay_division"]
+    delay_division: EnumParam<Division>,
+    #[id = "attack_division"]
+    attack_division: EnumParam<Division>,
+    #[id = "hold_division"]
+    hold_division: EnumParam<Division>,
+    #[id = "decay_division"]
+    decay_division: EnumParam<Division>,
+    #[id = "release_division"]
+    release_division: EnumParam<Division>,
+    #[id = "attack_curve"]
+    attack_curve: FloatParam,
+    #[id = "decay_curve"]
+    decay_curve: FloatParam,
+    #[id = "release_curve"]
+    release_curve: FloatParam,
+}
+
+#[derive(Params)]
+struct UnisonParams {
+    #[id = "unison"]
+    voices: IntParam,
+    #[id = "unison_detune"]
+    detune: FloatParam,
+    #[id = "unison_spread"]
+    spread: FloatParam,
+    #[id = "unison_random_phase"]
+    random_phase: BoolParam,
+}
+
+#[derive(Params)]
+struct NoiseParams {
+    #[id = "noise_level"]
+    level: FloatParam,
+    #[id = "noise_center"]
+    center: FloatParam, // relative to the note frequency
+    #[id = "noise_width"]
+    width: FloatParam,
+    #[id = "noise_delay"]
+    delay: FloatParam,
+    #[id = "noise_attack"]
+    attack: FloatParam,
+    #[id = "noise_decay"]
+    decay: FloatParam,
+    #[id = "noise_sustain"]
+    sustain: FloatParam,
+}
+
+#[derive(Params)]
+struct VelocityParams {
+    #[id = "velocity_curve"]
+    curve: EnumParam<VelocityCurve>,
+    #[id = "velocity_shape"]
+    shape: FloatParam,
+    #[id = "velocity_brightness"]
+    brightness: FloatParam, // tilt taken off the highs at zero velocity
+}
+
+#[derive(Params)]
+struct MorphParams {
+    #[id = "morph"]
+    amount: FloatParam,
+    #[id = "morph_velocity"]
+    velocity: FloatParam,
+    #[id = "morph_mod_wheel"]
+    mod_wheel: FloatParam,
+    #[id = "morph_envelope"]
+    envelope: FloatParam,
+    #[id = "morph_time"]
+    time: FloatParam, // the envelope ramps from 0 to 1 over this time after note on
+}
+
+#[derive(Params)]
+struct FmSlotParams {
+    #[id = "fm_source"]
+    source: IntParam, // partial numbers, 1 is the fundamental
+    #[id = "fm_target"]
+    target: IntParam,
+    #[id = "fm_index"]
+    index: FloatParam, // peak phase deviation in radians, 0 turns the slot off
+}
+
+#[derive(Params)]
+struct FmParams {
+    #[id = "fm_delay"]
+    delay: FloatParam,
+    #[id = "fm_attack"]
+    attack: FloatParam,
+    #[id = "fm_decay"]
+    decay: FloatParam,
+    #[id = "fm_sustain"]
+    sustain: FloatParam,
+    #[id = "fm_velocity"]
+    velocity: FloatParam,
+}
+
+#[derive(Params)]
+struct LfoParams {
+    #[id = "lfo_shape"]
+    shape: EnumParam<LfoShape>,
+    #[id = "lfo_rate"]
+    rate: FloatParam,
+    #[id = "lfo_sync"]
+    sync: BoolParam,
+    #[id = "lfo_beats"]
+    beats: FloatParam, // length of a cycle with the tempo sync
+    #[id = "lfo_phase"]
+    phase: FloatParam,
+    #[id = "lfo_mode"]
+    mode: EnumParam<LfoMode>,
+    #[id = "lfo_fade"]
+    fade: FloatParam, // fade in after note on
+    #[id = "lfo_pitch"]
+    pitch: FloatParam,
+    #[id = "lfo_gain"]
+    gain: FloatParam,
+    #[id = "lfo_overtone"]
+    overtone: IntParam, // 0 turns the overtone destination off
+    #[id = "lfo_overtone_depth"]
+    overtone_depth: FloatParam,
+    #[id = "lfo_envelope"]
+    envelope: FloatParam, // octaves, positive stretches the envelope times
+}
+
+#[derive(Params)]
+struct ModSlotParams {
+    #[id = "mod_source"]
+    source: EnumParam<ModSource>,
+    #[id = "mod_target"]
+    target: EnumParam<ModTarget>,
+    #[id = "mod_number"]
+    number: IntParam,
+    #[id = "mod_depth"]
+    depth: FloatParam, // fraction of the target's range, 0 turns the slot off
 }
 
 #[derive(Params)]
 struct OvertoneParams {
-    #[id = "overtone1"]
-    overtone1: FloatParam,
-    #[id = "overtone2"]
-    overtone2: FloatParam,
-    #[id = "overtone3"]
-    overtone3: FloatParam,
-    #[id = "overtone4"]
-    overtone4: FloatParam,
-    #[id = "overtone5"]
-    overtone5: FloatParam,
-    #[id = "overtone6"]
-    overtone6: FloatParam,
-    #[id = "overtone7"]
-    overtone7: FloatParam,
-    #[id = "overtone8"]
-    overtone8: FloatParam,
+    #[id = "overtone"]
+    amplitude: FloatParam,
+    #[id = "phase"]
+    phase: FloatParam,
+    #[id = "overtone_b"]
+    amplitude_b: FloatParam, // second spectrum for the morph
+    #[id = "phase_b"]
+    phase_b: FloatParam,
+    #[id = "ratio"]
+    ratio: FloatParam, // 0 follows the stretched harmonic series
+    #[id = "decay_scale"]
+    decay_scale: FloatParam,
+    #[id = "pan"]
+    pan: FloatParam,
+}
+
+#[derive(Params)]
+struct UndertoneParams {
+    #[id = "undertone"]
+    amplitude: FloatParam,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Partial {
+    amplitude: f32,
+    phase: f32,       // offset in cycles
+    amplitude_b: f32, // morph target
+    phase_delta: f32, // shortest way from phase to phase b, in cycles
+    ratio: f32,       // frequency relative to the fundamental
+    decay_scale: f32, // multiplier for decay and release times
+    pan_left: f32,
+    pan_right: f32,
+}
+
+// the amplitudes keep the `overtone1`..`overtone8` ids of the fixed overtone params, so saved
+// sessions and host automation still find them, the rest is numbered like a nested array
+struct Overtones([OvertoneParams; MAX_PARTIALS]);
+
+unsafe impl Params for Overtones {
+    fn param_map(&self) -> Vec<(String, ParamPtr, String)> {
+        self.0
+            .iter()
+            .enumerate()
+            .flat_map(|(i, overtone)| {
+                overtone
+                    .param_map()
+                    .into_iter()
+                    .map(move |(id, ptr, group)| {
+                        let id = if id == "overtone" {
+                            format!("overtone{}", i + 1)
+                        } else {
+                            format!("{id}_{}", i + 1)
+                        };
+                        let group = if group.is_empty() {
+                            format!("Overtone {}", i + 1)
+                        } else {
+                            format!("Overtone {}/{group}", i + 1)
+                        };
+                        (id, ptr, group)
+                    })
+            })
+            .collect()
+    }
+}
+
+impl std::ops::Deref for Overtones {
+    type Target = [OvertoneParams; MAX_PARTIALS];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FuririParams {
+    // active overtones followed by all undertones
+    fn partials(&self) -> impl Iterator<Item = Partial> + '_ {
+        self.partials_with_tilt(self.tilt.value())
+    }
+
+    // the tilt sweep export overrides the tilt, everything else comes from the current values
+    fn partials_with_tilt(&self, tilt: f32) -> impl Iterator<Item = Partial> + '_ {
+        let inharmonicity = self.inharmonicity.value();
+        let odd_even = self.odd_even.value();
+        self.overtones[..self.partial_count.value().count()]
+            .iter()
+            .enumerate()
+            .map(move |(i, overtone)| {
+                let harmonic = i + 1;
+                let gain =
+                    db_to_gain(tilt * (harmonic as f32).log2()) * balance(harmonic, odd_even);
+                overtone.partial(harmonic, inharmonicity, gain, current)
+            })
+            .chain(
+                self.undertones
+                    .iter()
+                    .enumerate()
+                    .map(|(i, undertone)| undertone.partial(i + 2, current)),
+            )
+    }
+
+    // the parameter behind a mod matrix target, `number` counts from 0 and is below
+    // `ModTarget::numbers`, none for the discrete targets, which `Steps` handles
+    fn mod_target(&self, target: ModTarget, number: usize) -> Option<&FloatParam> {
+        Some(match target {
+            ModTarget::BasePitch => &self.basepitch,
+            ModTarget::Gain => &self.gain,
+            ModTarget::Inharmonicity => &self.inharmonicity,
+            ModTarget::Tilt => &self.tilt,
+            ModTarget::OddEven => &self.odd_even,
+            ModTarget::Width => &self.width,
+            ModTarget::KeyPan => &self.key_pan,
+            ModTarget::RandomPan => &self.random_pan,
+            ModTarget::Glide => &self.glide_time,
+            ModTarget::GlideCurve => &self.glide_curve,
+            ModTarget::Detune => &self.unison.detune,
+            ModTarget::Spread => &self.unison.spread,
+            ModTarget::Overtone => &self.overtones[number].amplitude,
+            ModTarget::OvertoneB => &self.overtones[number].amplitude_b,
+            ModTarget::Phase => &self.overtones[number].phase,
+            ModTarget::PhaseB => &self.overtones[number].phase_b,
+            ModTarget::Ratio => &self.overtones[number].ratio,
+            ModTarget::DecayScale => &self.overtones[number].decay_scale,
+            ModTarget::Pan => &self.overtones[number].pan,
+            ModTarget::Undertone => &self.undertones[number].amplitude,
+            ModTarget::Delay => &self.envelope.delay,
+            ModTarget::Attack => &self.envelope.attack,
+            ModTarget::Hold => &self.envelope.hold,
+            ModTarget::Decay => &self.envelope.decay,
+            ModTarget::Sustain => &self.envelope.sustain,
+            ModTarget::Release => &self.envelope.release,
+            ModTarget::AttackCurve => &self.envelope.attack_curve,
+            ModTarget::DecayCurve => &self.envelope.decay_curve,
+            ModTarget::ReleaseCurve => &self.envelope.release_curve,
+            ModTarget::NoiseLevel => &self.noise.level,
+            ModTarget::NoiseCenter => &self.noise.center,
+            ModTarget::NoiseWidth => &self.noise.width,
+            ModTarget::NoiseAttack => &self.noise.attack,
+            ModTarget::NoiseDecay => &self.noise.decay,
+            ModTarget::NoiseSustain => &self.noise.sustain,
+            ModTarget::VelocityShape => &self.velocity.shape,
+            ModTarget::VelocityBrightness => &self.velocity.brightness,
+            ModTarget::Morph => &self.morph.amount,
+            ModTarget::MorphVelocity => &self.morph.velocity,
+            ModTarget::MorphModWheel => &self.morph.mod_wheel,
+            ModTarget::MorphEnvelope => &self.morph.envelope,
+            ModTarget::MorphTime => &self.morph.time,
+            ModTarget::FmIndex => &self.fm_slots[number].index,
+            ModTarget::FmAttack => &self.fm.attack,
+            ModTarget::FmDecay => &self.fm.decay,
+            ModTarget::FmSustain => &self.fm.sustain,
+            ModTarget::FmVelocity => &self.fm.velocity,
+            ModTarget::LfoRate => &self.lfos[number].rate,
+            ModTarget::LfoBeats => &self.lfos[number].beats,
+            ModTarget::LfoPhase => &self.lfos[number].phase,
+            ModTarget::LfoFade => &self.lfos[number].fade,
+            ModTarget::LfoPitch => &self.lfos[number].pitch,
+            ModTarget::LfoGain => &self.lfos[number].gain,
+            ModTarget::LfoOvertoneDepth => &self.lfos[number].overtone_depth,
+            ModTarget::LfoEnvelope => &self.lfos[number].envelope,
+            ModTarget::PartialCount
+            | ModTarget::UnisonVoices
+            | ModTarget::VelocityCurve
+            | ModTarget::FmSource
+            | ModTarget::FmTarget
+            | ModTarget::LfoShape
+            | ModTarget::LfoOvertone => return None,
+        })
+    }
+}
+
+// reads a parameter either as it is or through its smoother
+type Read = fn(&FloatParam) -> f32;
+
+fn current(param: &FloatParam) -> f32 {
+    param.value()
+}
+
+// only the smoothers that are moving take a step, the rest already sit at their target
+fn smoothed(param: &FloatParam) -> f32 {
+    if param.smoothed.is_smoothing() {
+        param.smoothed.next()
+    } else {
+        param.value()
+    }
+}
+
+// the shorter way around, between -0.5 and 0.5 cycles
+fn wrap_phase(cycles: f32) -> f32 {
+    (cycles + 0.5).rem_euclid(1.0) - 0.5
+}
+
+// -1 keeps only odd harmonics, 1 only even ones, the fundamental always stays so the pitch holds
+fn balance(harmonic: usize, odd_even: f32) -> f32 {
+    if harmonic == 1 {
+        1.0
+    } else if harmonic % 2 == 1 {
+        (1.0 - odd_even).min(1.0)
+    } else {
+        (1.0 + odd_even).min(1.0)
+    }
 }
 
 impl Default for FuririParams {
     fn default() -> Self {
         Self {
             editor_state: editor::default_state(),
+            imported_cycle: RwLock::new(Vec::new()),
             basepitch: FloatParam::new(
                 "Base Pitch",
                 440.0,
@@ -74,6 +702,7 @@ impl Default for FuririParams {
                     factor: 0.25,
                 },
             )
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
             .with_step_size(0.01)
             .with_unit(" Hz"),
             basenote: IntParam::new("Base Note", 69, IntRange::Linear { min: 0, max: 127 }),
@@ -86,159 +715,1531 @@ impl Default for FuririParams {
                     max: 0.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.1)
             .with_unit(" dB"),
-            overtones: OvertoneParams::default(),
-            envelope: EnvelopeParams::default(),
-        }
-    }
-}
-
-impl Default for OvertoneParams {
-    fn default() -> Self {
-        Self {
-            overtone1: FloatParam::new(
-                "Overtone 1",
-                1.0,
+            partial_count: EnumParam::new("Partials", PartialCount::Eight),
+            inharmonicity: FloatParam::new(
+                "Inharmonicity",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 0.05,
+                    factor: 0.3,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(5)),
+            tilt: FloatParam::new(
+                "Tilt",
+                0.0,
                 FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
+                    min: -12.0,
+                    max: 12.0,
                 },
             )
-            .with_step_size(0.01),
-            overtone2: FloatParam::new(
-                "Overtone 2",
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" dB/oct"),
+            odd_even: FloatParam::new(
+                "Odd/Even",
                 0.0,
                 FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
-            overtone3: FloatParam::new(
-                "Overtone 3",
+            width: FloatParam::new(
+                "Width",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 200.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(1.0)
+            .with_unit(" %"),
+            key_pan: FloatParam::new(
+                "Key Pan",
                 0.0,
                 FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            random_pan: FloatParam::new(
+                "Random Pan",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
-            overtone4: FloatParam::new(
-                "Overtone 4",
+            voice_mode: EnumParam::new("Voice Mode", VoiceMode::Poly),
+            note_priority: EnumParam::new("Note Priority", NotePriority::Last),
+            glide_time: FloatParam::new(
+                "Glide",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            glide_curve: FloatParam::new(
+                "Glide Curve",
                 0.0,
                 FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
-            overtone5: FloatParam::new(
-                "Overtone 5",
-                0.0,
+            polyphony: IntParam::new(
+                "Polyphony",
+                MAX_VOICES as i32,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_VOICES as i32,
+                },
+            ),
+            steal_policy: EnumParam::new("Steal Policy", StealPolicy::Oldest),
+            phase_mode: EnumParam::new("Phase Mode", PhaseMode::Reset),
+            partial_random_phase: BoolParam::new("Partial Random Phase", false),
+            overtones: Overtones(std::array::from_fn(OvertoneParams::new)),
+            undertones: std::array::from_fn(UndertoneParams::new),
+            envelope: EnvelopeParams::default(),
+            unison: UnisonParams::default(),
+            noise: NoiseParams::default(),
+            velocity: VelocityParams::default(),
+            morph: MorphParams::default(),
+            fm_slots: std::array::from_fn(FmSlotParams::new),
+            fm: FmParams::default(),
+            lfos: std::array::from_fn(LfoParams::new),
+            mod_slots: std::array::from_fn(ModSlotParams::new),
+        }
+    }
+}
+
+impl OvertoneParams {
+    fn new(index: usize) -> Self {
+        Self {
+            amplitude: FloatParam::new(
+                format!("Overtone {}", index + 1),
+                if index == 0 { 1.0 } else { 0.0 },
                 FloatRange::Linear {
                     min: -2.0,
                     max: 2.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
-            overtone6: FloatParam::new(
-                "Overtone 6",
+            // both phases are smoothed around the circle by the patch, see `PhaseSmoother`
+            phase: FloatParam::new(
+                format!("Phase {}", index + 1),
                 0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 360.0,
+                },
+            )
+            .with_step_size(1.0)
+            .with_unit("°"),
+            amplitude_b: FloatParam::new(
+                format!("Overtone {} B", index + 1),
+                if index == 0 { 1.0 } else { 0.0 },
                 FloatRange::Linear {
                     min: -2.0,
                     max: 2.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
-            overtone7: FloatParam::new(
-                "Overtone 7",
+            phase_b: FloatParam::new(
+                format!("Phase {} B", index + 1),
                 0.0,
                 FloatRange::Linear {
-                    min: -2.0,
-                    max: 2.0,
+                    min: 0.0,
+                    max: 360.0,
                 },
             )
-            .with_step_size(0.01),
-            overtone8: FloatParam::new(
-                "Overtone 8",
+            .with_step_size(1.0)
+            .with_unit("°"),
+            ratio: FloatParam::new(
+                format!("Ratio {}", index + 1),
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 32.0,
+                    factor: 0.5,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.001)
+            .with_value_to_string(Arc::new(|value| {
+                if value <= 0.0 {
+                    String::from("Auto")
+                } else {
+                    format!("{value:.3}")
+                }
+            }))
+            .with_string_to_value(Arc::new(|string| {
+                if string.trim().eq_ignore_ascii_case("auto") {
+                    Some(0.0)
+                } else {
+                    string.trim().parse().ok()
+                }
+            })),
+            decay_scale: FloatParam::new(
+                format!("Decay Scale {}", index + 1),
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 10.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
+            .with_step_size(0.01)
+            .with_unit("x"),
+            pan: FloatParam::new(
+                format!("Pan {}", index + 1),
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+        }
+    }
+
+    // harmonic is 1 for the fundamental, stretched like a stiff string: n * sqrt(1 + B * n^2),
+    // gain comes from the timbre macros
+    fn partial(&self, harmonic: usize, inharmonicity: f32, gain: f32, read: Read) -> Partial {
+        // the target of the smoother, 0 switches over to the harmonic series
+        let ratio = self.ratio.value();
+        let harmonic = harmonic as f32;
+        let (pan_left, pan_right) = pan_gains(read(&self.pan));
+        let phase = read(&self.phase) / 360.0;
+        Partial {
+            amplitude: read(&self.amplitude) * gain,
+            phase,
+            amplitude_b: read(&self.amplitude_b) * gain,
+            phase_delta: wrap_phase(read(&self.phase_b) / 360.0 - phase),
+            ratio: if ratio > 0.0 {
+                ratio
+            } else {
+                harmonic * (1.0 + inharmonicity * harmonic * harmonic).sqrt()
+            },
+            decay_scale: read(&self.decay_scale),
+            pan_left,
+            pan_right,
+        }
+    }
+
+    // `partial` for the patch, which runs every sample, so the derived values are only
+    // recomputed when their inputs move, returns whether the ratio changed
+    fn update(
+        &self,
+        p: &mut Partial,
+        state: &mut OvertoneState,
+        harmonic: usize,
+        inharmonicity: f32,
+        read: Read,
+        phase_steps: u32,
+    ) -> bool {
+        p.amplitude = read(&self.amplitude);
+        p.amplitude_b = read(&self.amplitude_b);
+        p.decay_scale = read(&self.decay_scale);
+        let pan = read(&self.pan);
+        if pan != state.pan {
+            state.pan = pan;
+            (p.pan_left, p.pan_right) = pan_gains(pan);
+        }
+        let phase_moved = state.phase.next(self.phase.value() / 360.0, phase_steps);
+        let phase_b_moved = state
+            .phase_b
+            .next(self.phase_b.value() / 360.0, phase_steps);
+        if phase_moved || phase_b_moved {
+            p.phase = state.phase.value;
+            p.phase_delta = wrap_phase(state.phase_b.value - state.phase.value);
+        }
+        // glides between two set ratios, switching to or from the series jumps
+        let target = self.ratio.value();
+        let ratio = if target > 0.0 && state.ratio > 0.0 {
+            read(&self.ratio)
+        } else {
+            if target != state.ratio {
+                self.ratio.smoothed.reset(target);
+            }
+            target
+        };
+        if ratio == state.ratio && inharmonicity == state.inharmonicity {
+            return false;
+        }
+        state.ratio = ratio;
+        state.inharmonicity = inharmonicity;
+        let harmonic = harmonic as f32;
+        p.ratio = if ratio > 0.0 {
+            ratio
+        } else {
+            harmonic * (1.0 + inharmonicity * harmonic * harmonic).sqrt()
+        };
+        true
+    }
+}
+
+impl UndertoneParams {
+    fn new(index: usize) -> Self {
+        Self {
+            amplitude: FloatParam::new(
+                format!("Undertone 1/{}", index + 2),
                 0.0,
                 FloatRange::Linear {
                     min: -2.0,
                     max: 2.0,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.01),
         }
     }
+
+    // divisor is 2 for the first undertone, an octave below, untouched by the timbre macros
+    fn partial(&self, divisor: usize, read: Read) -> Partial {
+        let amplitude = read(&self.amplitude);
+        Partial {
+            amplitude,
+            phase: 0.0,
+            amplitude_b: amplitude,
+            phase_delta: 0.0,
+            ratio: 1.0 / divisor as f32,
+            decay_scale: 1.0,
+            pan_left: 1.0,
+            pan_right: 1.0,
+        }
+    }
+}
+
+impl Partial {
+    // 0 is spectrum a, 1 is spectrum b
+    fn morphed(&self, morph: f32) -> Self {
+        let amplitude = self.amplitude + (self.amplitude_b - self.amplitude) * morph;
+        Self {
+            amplitude,
+            phase: self.phase + self.phase_delta * morph,
+            amplitude_b: amplitude,
+            phase_delta: 0.0,
+            ..*self
+        }
+    }
 }
 
 impl Default for EnvelopeParams {
     fn default() -> Self {
         Self {
-            attack: FloatParam::new(
-                "Attack",
-                1.0,
+            delay: envelope_time("Delay", 0.0),
+            attack: envelope_time("Attack", 1.0),
+            hold: envelope_time("Hold", 0.0),
+            decay: envelope_time("Decay", 0.0),
+            sustain: FloatParam::new("Sustain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01),
+            release: envelope_time("Release", 20.0),
+            sync: BoolParam::new("Tempo Sync", false),
+            delay_division: EnumParam::new("Delay Division", Division::Off),
+            attack_division: EnumParam::new("Attack Division", Division::Off),
+            hold_division: EnumParam::new("Hold Division", Division::Off),
+            decay_division: EnumParam::new("Decay Division", Division::Off),
+            release_division: EnumParam::new("Release Division", Division::SixtyFourth),
+            attack_curve: FloatParam::new(
+                "Attack Curve",
+                0.0,
                 FloatRange::Linear {
-                    min: 0.0,
-                    max: 500.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
-            .with_step_size(0.1)
-            .with_unit(" ms"),
-            decay: FloatParam::new(
-                "Decay",
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            decay_curve: FloatParam::new(
+                "Decay Curve",
                 0.0,
                 FloatRange::Linear {
-                    min: 0.0,
-                    max: 500.0,
+                    min: -1.0,
+                    max: 1.0,
                 },
             )
-            .with_step_size(0.1)
-            .with_unit(" ms"),
-            sustain: FloatParam::new("Sustain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
-                .with_step_size(0.01),
-            release: FloatParam::new(
-                "Release",
-                20.0,
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            release_curve: FloatParam::new(
+                "Release Curve",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl ModTarget {
+    // how many of the target there are, the mod slot number is clamped to these
+    fn numbers(&self) -> usize {
+        match self {
+            ModTarget::Overtone
+            | ModTarget::OvertoneB
+            | ModTarget::Phase
+            | ModTarget::PhaseB
+            | ModTarget::Ratio
+            | ModTarget::DecayScale
+            | ModTarget::Pan => MAX_PARTIALS,
+            ModTarget::Undertone => MAX_UNDERTONES,
+            ModTarget::FmIndex | ModTarget::FmSource | ModTarget::FmTarget => MAX_FM_SLOTS,
+            ModTarget::LfoRate
+            | ModTarget::LfoBeats
+            | ModTarget::LfoPhase
+            | ModTarget::LfoFade
+            | ModTarget::LfoPitch
+            | ModTarget::LfoGain
+            | ModTarget::LfoOvertoneDepth
+            | ModTarget::LfoEnvelope
+            | ModTarget::LfoShape
+            | ModTarget::LfoOvertone => MAX_LFOS,
+            _ => 1,
+        }
+    }
+}
+
+impl Division {
+    fn beats(&self) -> Option<f32> {
+        Some(match self {
+            Division::Off => return None,
+            Division::SixtyFourth => 1.0 / 16.0,
+            Division::ThirtySecond => 1.0 / 8.0,
+            Division::Sixteenth => 1.0 / 4.0,
+            Division::Eighth => 1.0 / 2.0,
+            Division::Quarter => 1.0,
+            Division::Half => 2.0,
+            Division::Bar => 4.0,
+            Division::TwoBars => 8.0,
+            Division::FourBars => 16.0,
+            Division::EightBars => 32.0,
+        })
+    }
+}
+
+// milliseconds, seconds can be typed in as well
+fn envelope_time(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 0.0,
+            max: MAX_ENVELOPE_TIME,
+            factor: FloatRange::skew_factor(-2.5),
+        },
+    )
+    .with_smoother(SmoothingStyle::Linear(10.0))
+    .with_step_size(0.1)
+    .with_value_to_string(Arc::new(|value| format!("{value:.1} ms")))
+    .with_string_to_value(Arc::new(|string| {
+        let string = string.trim();
+        match string.strip_suffix("ms") {
+            Some(ms) => ms.trim().parse().ok(),
+            None => match string.strip_suffix('s') {
+                Some(seconds) => seconds.trim().parse().ok().map(|s: f32| s * 1000.0),
+                None => string.parse().ok(),
+            },
+        }
+    }))
+}
+
+impl Default for UnisonParams {
+    fn default() -> Self {
+        Self {
+            voices: IntParam::new(
+                "Unison",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_UNISON as i32,
+                },
+            ),
+            detune: FloatParam::new(
+                "Detune",
+                10.0,
                 FloatRange::Skewed {
                     min: 0.0,
-                    max: 5000.0,
+                    max: 100.0,
                     factor: 0.5,
                 },
             )
+            .with_smoother(SmoothingStyle::Linear(10.0))
             .with_step_size(0.1)
-            .with_unit(" ms"),
+            .with_unit(" ct"),
+            spread: FloatParam::new("Spread", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01),
+            random_phase: BoolParam::new("Random Phase", true),
         }
     }
 }
 
-const PITCH_RANGE: f32 = 2.0; // semitones
-const MAX_VOICES: usize = 64;
-
+impl Default for VelocityParams {
+    fn default() -> Self {
+        Self {
+            curve: EnumParam::new("Velocity Curve", VelocityCurve::Linear),
+            shape: FloatParam::new(
+                "Velocity Shape",
+                0.0,
+                FloatRange::Linear {
+                    min: -2.0,
+                    max: 2.0,
+                },
+            )
+            .with_step_size(0.01),
+            brightness: FloatParam::new(
+                "Velocity Brightness",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" dB/oct"),
+        }
+    }
+}
+
+impl VelocityCurve {
+    // velocity and result from 0 to 1
+    fn apply(&self, velocity: f32, shape: f32) -> f32 {
+        match self {
+            VelocityCurve::Linear => velocity,
+            VelocityCurve::Exponential => {
+                (VELOCITY_CURVE * velocity).exp_m1() / VELOCITY_CURVE.exp_m1()
+            }
+            VelocityCurve::Logarithmic => {
+                (velocity * VELOCITY_CURVE.exp_m1()).ln_1p() / VELOCITY_CURVE
+            }
+            VelocityCurve::Custom => velocity.powf(2.0f32.powf(shape)),
+        }
+    }
+}
+
+impl Default for MorphParams {
+    fn default() -> Self {
+        Self {
+            amount: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01),
+            velocity: FloatParam::new(
+                "Morph Velocity",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            mod_wheel: FloatParam::new(
+                "Morph Mod Wheel",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            envelope: FloatParam::new(
+                "Morph Envelope",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            time: FloatParam::new(
+                "Morph Time",
+                1000.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 10000.0,
+                    factor: 0.3,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(1.0)
+            .with_unit(" ms"),
+        }
+    }
+}
+
+impl Default for NoiseParams {
+    fn default() -> Self {
+        Self {
+            level: FloatParam::new("Noise", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01),
+            center: FloatParam::new(
+                "Noise Center",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 16.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
+            .with_step_size(0.01)
+            .with_unit("x"),
+            width: FloatParam::new(
+                "Noise Width",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 6.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_unit(" oct"),
+            delay: envelope_time("Noise Delay", 0.0),
+            attack: FloatParam::new(
+                "Noise Attack",
+                1.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 500.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            decay: FloatParam::new(
+                "Noise Decay",
+                100.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 500.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            sustain: FloatParam::new(
+                "Noise Sustain",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl FmSlotParams {
+    fn new(index: usize) -> Self {
+        Self {
+            source: IntParam::new(
+                format!("FM {} Source", index + 1),
+                2,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_PARTIALS as i32,
+                },
+            ),
+            target: IntParam::new(
+                format!("FM {} Target", index + 1),
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_PARTIALS as i32,
+                },
+            ),
+            index: FloatParam::new(
+                format!("FM {} Index", index + 1),
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 20.0,
+                    factor: 0.5,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl Default for FmParams {
+    fn default() -> Self {
+        Self {
+            delay: envelope_time("FM Delay", 0.0),
+            attack: FloatParam::new(
+                "FM Attack",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 500.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            decay: FloatParam::new(
+                "FM Decay",
+                200.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 5000.0,
+                    factor: 0.5,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" ms"),
+            sustain: FloatParam::new("FM Sustain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(10.0))
+                .with_step_size(0.01),
+            velocity: FloatParam::new(
+                "FM Velocity",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+impl LfoParams {
+    fn new(index: usize) -> Self {
+        let name = |param: &str| format!("LFO {} {param}", index + 1);
+        Self {
+            shape: EnumParam::new(name("Shape"), LfoShape::Sine),
+            rate: FloatParam::new(
+                name("Rate"),
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 50.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(10.0))
+            .with_step_size(0.01)
+            .with_unit(" Hz"),
+            sync: BoolParam::new(name("Sync"), false),
+            beats: FloatParam::new(
+                name("Beats"),
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.25,
+                    max: 32.0,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.25)
+            .with_unit(" beats"),
+            phase: FloatParam::new(
+                name("Phase"),
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 360.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(1.0)
+            .with_unit("°"),
+            mode: EnumParam::new(name("Mode"), LfoMode::Voice),
+            fade: FloatParam::new(
+                name("Fade"),
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 10000.0,
+                    factor: 0.3,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(1.0)
+            .with_unit(" ms"),
+            pitch: FloatParam::new(
+                name("Pitch"),
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_unit(" st"),
+            gain: FloatParam::new(
+                name("Gain"),
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.1)
+            .with_unit(" dB"),
+            overtone: IntParam::new(
+                name("Overtone"),
+                0,
+                IntRange::Linear {
+                    min: 0,
+                    max: MAX_PARTIALS as i32,
+                },
+            )
+            .with_value_to_string(Arc::new(|value| match value {
+                0 => String::from("Off"),
+                _ => value.to_string(),
+            }))
+            .with_string_to_value(Arc::new(|string| match string.trim() {
+                "Off" => Some(0),
+                string => string.parse().ok(),
+            })),
+            overtone_depth: FloatParam::new(
+                name("Overtone Depth"),
+                0.0,
+                FloatRange::Linear {
+                    min: -2.0,
+                    max: 2.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+            envelope: FloatParam::new(
+                name("Envelope"),
+                0.0,
+                FloatRange::Linear {
+                    min: -4.0,
+                    max: 4.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01)
+            .with_unit(" oct"),
+        }
+    }
+}
+
+impl ModSlotParams {
+    fn new(index: usize) -> Self {
+        Self {
+            source: EnumParam::new(format!("Mod {} Source", index + 1), ModSource::Velocity),
+            target: EnumParam::new(format!("Mod {} Target", index + 1), ModTarget::Gain),
+            number: IntParam::new(
+                format!("Mod {} Number", index + 1),
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_PARTIALS as i32,
+                },
+            ),
+            depth: FloatParam::new(
+                format!("Mod {} Depth", index + 1),
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(10.0))
+            .with_step_size(0.01),
+        }
+    }
+}
+
+const PITCH_RANGE: f32 = 2.0; // semitones
+const MAX_VOICES: usize = 64;
+const MAX_NOTES: usize = 2 * MAX_VOICES; // room for stolen notes while they fade out
+const STEAL_FADE: f32 = 0.005; // seconds
+const MAX_PARTIALS: usize = 64;
+const MAX_UNDERTONES: usize = 8;
+const MAX_SPECTRUM: usize = MAX_PARTIALS + MAX_UNDERTONES;
+const MAX_UNISON: usize = 8;
+const MAX_FM_SLOTS: usize = 4;
+const MAX_LFOS: usize = 2;
+const MAX_MOD_SLOTS: usize = 8;
+const VELOCITY_CURVE: f32 = 4.0; // steepness of the exponential and logarithmic curves
+const MAX_ENVELOPE_TIME: f32 = 30000.0; // ms
+const CURVE_STEEPNESS: f32 = 6.0; // exponent of the envelope segments at full curvature
+const BANDLIMIT_FADE: f32 = 0.8; // fraction of nyquist where partials start fading out
+const PHASE_SMOOTHING: f32 = 10.0; // ms, like the smoothers of the other overtone parameters
+const FILTER_INTERVAL: u32 = 32; // samples between updates of the brightness and noise band
+
+fn bandlimit_gain(freq: f32, nyquist: f32) -> f32 {
+    ((nyquist - freq) / (nyquist * (1.0 - BANDLIMIT_FADE))).clamp(0.0, 1.0)
+}
+
+// balance law, keeps centred signals at full level in both channels
+fn pan_gains(pan: f32) -> (f32, f32) {
+    ((1.0 - pan).min(1.0), (1.0 + pan).min(1.0))
+}
+
+// times in seconds, curves from -1 (slow start) through 0 (linear)
+// to 1 (fast start and a long tail, like an analogue envelope)
+#[derive(Clone, Copy, Default)]
+struct Envelope {
+    delay: f32,
+    attack: f32,
+    hold: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    attack_curve: f32,
+    decay_curve: f32,
+    release_curve: f32,
+}
+
+impl Envelope {
+    fn new(params: &EnvelopeParams, tempo: Option<f64>, read: Read) -> Self {
+        // seconds per beat when synced to the host tempo
+        let beat = tempo
+            .filter(|&tempo| params.sync.value() && tempo > 0.0)
+            .map(|tempo| 60.0 / tempo as f32);
+        let time = |param: &FloatParam, division: &EnumParam<Division>| {
+            // read either way so the smoother keeps up
+            let ms = read(param);
+            match (beat, division.value().beats()) {
+                (Some(beat), Some(beats)) => beats * beat,
+                _ => ms / 1000.0,
+            }
+        };
+        Self {
+            delay: time(&params.delay, &params.delay_division),
+            attack: time(&params.attack, &params.attack_division),
+            hold: time(&params.hold, &params.hold_division),
+            decay: time(&params.decay, &params.decay_division),
+            sustain: read(&params.sustain),
+            release: time(&params.release, &params.release_division),
+            attack_curve: read(&params.attack_curve),
+            decay_curve: read(&params.decay_curve),
+            release_curve: read(&params.release_curve),
+        }
+    }
+
+    // with the offsets of the mod matrix, kept in range
+    fn offset(&self, by: &Envelope) -> Self {
+        Self {
+            delay: (self.delay + by.delay).max(0.0),
+            attack: (self.attack + by.attack).max(0.0),
+            hold: (self.hold + by.hold).max(0.0),
+            decay: (self.decay + by.decay).max(0.0),
+            sustain: (self.sustain + by.sustain).clamp(0.0, 1.0),
+            release: (self.release + by.release).max(0.0),
+            attack_curve: (self.attack_curve + by.attack_curve).clamp(-1.0, 1.0),
+            decay_curve: (self.decay_curve + by.decay_curve).clamp(-1.0, 1.0),
+            release_curve: (self.release_curve + by.release_curve).clamp(-1.0, 1.0),
+        }
+    }
+
+    // where the attack reaches `level`, a retrigger carries on from there instead of dropping
+    fn attack_time(&self, level: f32) -> f64 {
+        let progress = segment_curve_inverse(level.clamp(0.0, 1.0), self.attack_curve);
+        (self.delay + self.attack * progress) as f64
+    }
+}
+
+// fraction of a segment covered after `progress` of its time
+fn segment_curve(progress: f32, curvature: f32) -> f32 {
+    let k = curvature * CURVE_STEEPNESS;
+    if k.abs() < 1e-3 {
+        progress
+    } else {
+        (-k * progress).exp_m1() / (-k).exp_m1()
+    }
+}
+
+// progress at which a segment reaches `fraction`
+fn segment_curve_inverse(fraction: f32, curvature: f32) -> f32 {
+    let k = curvature * CURVE_STEEPNESS;
+    if k.abs() < 1e-3 {
+        fraction
+    } else {
+        -(fraction * (-k).exp_m1()).ln_1p() / k
+    }
+}
+
+fn envelope_level(
+    off: bool,
+    envelope_time: f32,
+    envelope: &Envelope,
+    release_envelope: f32,
+    decay_scale: f32,
+) -> f32 {
+    let decay = envelope.decay * decay_scale;
+    let release = envelope.release * decay_scale;
+    if off {
+        if envelope_time >= release {
+            0.0
+        } else {
+            release_envelope
+                * (1.0 - segment_curve(envelope_time / release, envelope.release_curve))
+        }
+    } else {
+        let time = envelope_time - envelope.delay;
+        let decay_start = envelope.attack + envelope.hold;
+        if time < 0.0 {
+            0.0
+        } else if time < envelope.attack {
+            segment_curve(time / envelope.attack, envelope.attack_curve)
+        } else if time < decay_start {
+            1.0
+        } else if time < decay_start + decay {
+            let s1 = 1.0 - envelope.sustain;
+            let progress = (time - decay_start) / decay;
+            1.0 - s1 * segment_curve(progress, envelope.decay_curve)
+        } else {
+            envelope.sustain
+        }
+    }
+}
+
+// parameter values, updated every sample so the smoothers advance, it carries over between
+// blocks so the smoothing and the cached values survive
+#[derive(Clone, Copy)]
+struct Patch {
+    overtones: [Partial; MAX_SPECTRUM], // undertones sit at MAX_PARTIALS.. whatever the count
+    overtone_count: usize,              // all of them while the mod matrix moves the partial count
+    overtone_limit: usize,              // the partial count as set, notes go silent from here on
+    overtone_states: [OvertoneState; MAX_PARTIALS], // every overtone, active or not
+    ratios_version: u32,                // goes up whenever a ratio moves, for the brightness
+    phase_steps: u32,                   // length of the phase smoothing in samples
+    inharmonicity: f32,
+    tilt: f32,
+    tilt_gains: [f32; MAX_PARTIALS], // cached, the tilt only moves while it's being automated
+    odd_even: f32, // tilt and balance are left out of the overtone amplitudes, see `Modulation`
+    basepitch: f32,
+    gain: f32, // dB
+    width: f32,
+    key_pan: f32,
+    random_pan: f32,
+    detune: f32, // cents
+    spread: f32,
+    spread_version: u32, // goes up whenever one of the four above moves, notes spread again
+    envelope: Envelope,
+    noise_level: f32,
+    noise_center: f32,
+    noise_width: f32,
+    noise_envelope: Envelope, // release and curves follow the main envelope
+    morph: f32,
+    morph_velocity: f32,
+    morph_mod_wheel: f32,
+    morph_envelope: f32,
+    morph_time: f32,
+    fm_slots: [FmSlot; MAX_FM_SLOTS],
+    fm_slot_count: usize,
+    fm_envelope: Envelope, // release and curves follow the main envelope
+    fm_velocity: f32,
+    velocity_brightness: f32,
+    glide_time: f32,
+    glide_curve: f32,
+    polyphony: usize,
+    steal_policy: StealPolicy,
+    lfos: [LfoSettings; MAX_LFOS],
+    mod_slots: [ModSlot; MAX_MOD_SLOTS],
+    mod_slot_count: usize,
+}
+
+// the inputs the patch last computed an overtone's derived values from
+#[derive(Clone, Copy)]
+struct OvertoneState {
+    pan: f32,
+    ratio: f32, // the parameter as read, 0 follows the stretched series
+    inharmonicity: f32,
+    phase: PhaseSmoother,
+    phase_b: PhaseSmoother,
+}
+
+impl Default for OvertoneState {
+    fn default() -> Self {
+        // nan so the first update fills in everything
+        Self {
+            pan: f32::NAN,
+            ratio: f32::NAN,
+            inharmonicity: f32::NAN,
+            phase: PhaseSmoother::default(),
+            phase_b: PhaseSmoother::default(),
+        }
+    }
+}
+
+// a linear smoother that takes the shorter way around the circle, a plain one would sweep
+// through the whole cycle going from 350° to 10°
+#[derive(Clone, Copy, Default)]
+struct PhaseSmoother {
+    value: f32, // in cycles
+    target: f32,
+    step: f32,
+    steps_left: u32,
+}
+
+impl PhaseSmoother {
+    // true while the value moves, with 0 steps it jumps straight to the target
+    fn next(&mut self, target: f32, steps: u32) -> bool {
+        if target != self.target {
+            self.target = target;
+            self.steps_left = steps.max(1);
+            self.step = wrap_phase(target - self.value) / self.steps_left as f32;
+        }
+        if self.steps_left == 0 {
+            return false;
+        }
+        self.steps_left -= 1;
+        self.value = if self.steps_left == 0 {
+            self.target
+        } else {
+            (self.value + self.step).rem_euclid(1.0)
+        };
+        true
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FmSlot {
+    slot: usize,   // which of the fm slot parameters
+    source: usize, // index into the partials
+    target: usize,
+    index: f32, // in cycles
+}
+
+#[derive(Clone, Copy, Default)]
+struct ModSlot {
+    source: ModSource,
+    target: ModTarget,
+    number: usize, // partial, fm slot or lfo, from 0 and within `ModTarget::numbers`
+    depth: f32,
+}
+
+#[derive(Clone, Copy, Default)]
+struct LfoSettings {
+    shape: LfoShape,
+    rate: f32, // Hz, follows the host tempo with the sync
+    synced: bool,
+    phase: f32, // in cycles
+    per_voice: bool,
+    fade: f32, // seconds
+    pitch: f32,
+    gain: f32,
+    overtone: Option<usize>, // index into the partials
+    overtone_depth: f32,
+    envelope: f32,
+}
+
+impl Patch {
+    // the current values without touching the smoothers
+    fn new(params: &FuririParams, tempo: Option<f64>, sample_rate: f32) -> Self {
+        let envelope = Envelope::new(&params.envelope, tempo, current);
+        let mut patch = Self {
+            overtones: [Partial::default(); MAX_SPECTRUM],
+            overtone_count: 0,
+            overtone_limit: 0,
+            overtone_states: [OvertoneState::default(); MAX_PARTIALS],
+            ratios_version: 0,
+            phase_steps: 0,
+            inharmonicity: 0.0,
+            tilt: f32::NAN,
+            tilt_gains: [1.0; MAX_PARTIALS],
+            odd_even: 0.0,
+            basepitch: 0.0,
+            gain: 0.0,
+            width: 0.0,
+            key_pan: 0.0,
+            random_pan: 0.0,
+            detune: 0.0,
+            spread: 0.0,
+            spread_version: 0,
+            envelope,
+            noise_level: 0.0,
+            noise_center: 0.0,
+            noise_width: 0.0,
+            noise_envelope: envelope,
+            morph: 0.0,
+            morph_velocity: 0.0,
+            morph_mod_wheel: 0.0,
+            morph_envelope: 0.0,
+            morph_time: 0.0,
+            fm_slots: [FmSlot::default(); MAX_FM_SLOTS],
+            fm_slot_count: 0,
+            fm_envelope: envelope,
+            fm_velocity: 0.0,
+            velocity_brightness: 0.0,
+            glide_time: 0.0,
+            glide_curve: 0.0,
+            polyphony: MAX_VOICES,
+            steal_policy: StealPolicy::Oldest,
+            lfos: [LfoSettings::default(); MAX_LFOS],
+            mod_slots: [ModSlot::default(); MAX_MOD_SLOTS],
+            mod_slot_count: 0,
+        };
+        patch.update(params, tempo, current);
+        patch.phase_steps = (PHASE_SMOOTHING / 1000.0 * sample_rate) as u32;
+        patch
+    }
+
+    // with `smoothed` every smoother takes one step, so call it exactly once per sample
+    fn update(&mut self, params: &FuririParams, tempo: Option<f64>, read: Read) {
+        self.mod_slot_count = 0;
+        for slot in params.mod_slots.iter() {
+            let depth = read(&slot.depth);
+            if depth != 0.0 {
+                let target = slot.target.value();
+                self.mod_slots[self.mod_slot_count] = ModSlot {
+                    source: slot.source.value(),
+                    target,
+                    number: (slot.number.value() as usize - 1).min(target.numbers() - 1),
+                    depth,
+                };
+                self.mod_slot_count += 1;
+            }
+        }
+
+        let inharmonicity = read(&params.inharmonicity);
+        let tilt = read(&params.tilt);
+        self.inharmonicity = inharmonicity;
+        self.odd_even = read(&params.odd_even);
+        if tilt != self.tilt {
+            self.tilt = tilt;
+            for (n, gain) in self.tilt_gains.iter_mut().enumerate() {
+                *gain = db_to_gain(tilt * ((n + 1) as f32).log2());
+            }
+        }
+        self.overtone_limit = params.partial_count.value().count();
+        let overtone_count = if self.modulates_any(ModTarget::PartialCount) {
+            MAX_PARTIALS
+        } else {
+            self.overtone_limit
+        };
+        let mut ratios_changed = overtone_count != self.overtone_count;
+        // the inactive overtones keep smoothing too, so raising the count doesn't jump
+        let (overtones, undertones) = self.overtones.split_at_mut(MAX_PARTIALS);
+        for (i, ((partial, state), overtone)) in overtones
+            .iter_mut()
+            .zip(&mut self.overtone_states)
+            .zip(params.overtones.iter())
+            .enumerate()
+        {
+            ratios_changed |=
+                overtone.update(partial, state, i + 1, inharmonicity, read, self.phase_steps);
+        }
+        if ratios_changed {
+            self.ratios_version = self.ratios_version.wrapping_add(1);
+        }
+        for (i, (partial, undertone)) in undertones.iter_mut().zip(&params.undertones).enumerate() {
+            *partial = undertone.partial(i + 2, read);
+        }
+        self.overtone_count = overtone_count;
+
+        self.basepitch = read(&params.basepitch);
+        self.gain = read(&params.gain);
+        self.width = read(&params.width);
+        let spread = (
+            read(&params.key_pan),
+            read(&params.random_pan),
+            read(&params.unison.detune),
+            read(&params.unison.spread),
+        );
+        if spread != (self.key_pan, self.random_pan, self.detune, self.spread) {
+            (self.key_pan, self.random_pan, self.detune, self.spread) = spread;
+            self.spread_version = self.spread_version.wrapping_add(1);
+        }
+
+        self.envelope = Envelope::new(&params.envelope, tempo, read);
+        self.noise_level = read(&params.noise.level);
+        self.noise_center = read(&params.noise.center);
+        self.noise_width = read(&params.noise.width);
+        self.noise_envelope = Envelope {
+            delay: read(&params.noise.delay) / 1000.0,
+            attack: read(&params.noise.attack) / 1000.0,
+            hold: 0.0,
+            decay: read(&params.noise.decay) / 1000.0,
+            sustain: read(&params.noise.sustain),
+            ..self.envelope
+        };
+
+        self.morph = read(&params.morph.amount);
+        self.morph_velocity = read(&params.morph.velocity);
+        self.morph_mod_wheel = read(&params.morph.mod_wheel);
+        self.morph_envelope = read(&params.morph.envelope);
+        self.morph_time = read(&params.morph.time) / 1000.0;
+
+        // slots that are off or point past the active overtones are skipped, unless the mod
+        // matrix can still turn them on
+        self.fm_slot_count = 0;
+        for (k, slot) in params.fm_slots.iter().enumerate() {
+            let source = slot.source.value() as usize - 1;
+            let target = slot.target.value() as usize - 1;
+            let index = read(&slot.index);
+            let active = index > 0.0 || self.modulates(ModTarget::FmIndex, k);
+            let routed =
+                self.modulates(ModTarget::FmSource, k) || self.modulates(ModTarget::FmTarget, k);
+            if active && (routed || (source < overtone_count && target < overtone_count)) {
+                self.fm_slots[self.fm_slot_count] = FmSlot {
+                    slot: k,
+                    source,
+                    target,
+                    index: index / TAU,
+                };
+                self.fm_slot_count += 1;
+            }
+        }
+        self.fm_envelope = Envelope {
+            delay: read(&params.fm.delay) / 1000.0,
+            attack: read(&params.fm.attack) / 1000.0,
+            hold: 0.0,
+            decay: read(&params.fm.decay) / 1000.0,
+            sustain: read(&params.fm.sustain),
+            ..self.envelope
+        };
+        self.fm_velocity = read(&params.fm.velocity);
+
+        self.velocity_brightness = read(&params.velocity.brightness);
+        self.glide_time = read(&params.glide_time) / 1000.0;
+        self.glide_curve = read(&params.glide_curve);
+        self.polyphony = params.polyphony.value() as usize;
+        self.steal_policy = params.steal_policy.value();
+
+        for (settings, lfo) in self.lfos.iter_mut().zip(&params.lfos) {
+            let rate = read(&lfo.rate);
+            let beats = read(&lfo.beats);
+            let overtone = lfo.overtone.value() as usize;
+            let tempo = tempo.filter(|&tempo| lfo.sync.value() && tempo > 0.0);
+            *settings = LfoSettings {
+                shape: lfo.shape.value(),
+                rate: tempo.map_or(rate, |tempo| tempo as f32 / 60.0 / beats),
+                synced: tempo.is_some(),
+                phase: read(&lfo.phase) / 360.0,
+                per_voice: lfo.mode.value() == LfoMode::Voice,
+                fade: read(&lfo.fade) / 1000.0,
+                pitch: read(&lfo.pitch),
+                gain: read(&lfo.gain),
+                overtone: overtone.checked_sub(1).filter(|&i| i < overtone_count),
+                overtone_depth: read(&lfo.overtone_depth),
+                envelope: read(&lfo.envelope),
+            };
+        }
+    }
+
+    // slot and values of every partial that sounds, the active overtones and all undertones
+    fn partials(&self) -> impl Iterator<Item = (usize, &Partial)> + '_ {
+        (0..self.overtone_count)
+            .chain(MAX_PARTIALS..MAX_SPECTRUM)
+            .map(move |i| (i, &self.overtones[i]))
+    }
+
+    fn fm_slots(&self) -> &[FmSlot] {
+        &self.fm_slots[..self.fm_slot_count]
+    }
+
+    fn mod_slots(&self) -> &[ModSlot] {
+        &self.mod_slots[..self.mod_slot_count]
+    }
+
+    fn modulates(&self, target: ModTarget, number: usize) -> bool {
+        self.mod_slots()
+            .iter()
+            .any(|slot| slot.target == target && slot.number == number)
+    }
+
+    fn modulates_any(&self, target: ModTarget) -> bool {
+        self.mod_slots().iter().any(|slot| slot.target == target)
+    }
+}
+
 pub struct Furiri {
     params: Arc<FuririParams>,
     current_notes: Vec<Note>,
     sample_rate: f32,
-    pitch_bend: f32,
+    pitch_bend_ratio: f32,
+    held_notes: Vec<u8>, // keys that are down, oldest first
     sustain_pedal: bool,
+    controls: Controls,
+    notes_started: u64,    // numbers the notes, the vector isn't kept in order
+    clock: f64,            // seconds since initialize, for free running phases
+    lfos: [Lfo; MAX_LFOS], // the global ones, every note keeps its own for per voice mode
+    patch: Patch,
+    sine: SineTable,
+    rng: Rng,
 }
 
 struct Note {
+    started: u64, // higher is newer
     note: u8,
     velocity: u8,
-    phase: f32,
-    samples_since_event: usize, // updated per block
-    release_envelope: f32,      // envelope value at note off
+    velocity_gain: f32,               // velocity through the curve
+    brightness: [f32; MAX_SPECTRUM],  // gain of each partial from velocity
+    brightness_from: (f32, f32, u32), // brightness, velocity gain and ratios behind the gains
+    band: (f32, f32),                 // center and width the noise filter is set to
+    filter_countdown: u32,            // samples until the brightness and band are checked again
+    ratio: f32,                       // frequency relative to basepitch, updated per block
+    glide_from: f32,                  // ratio the glide started at
+    glide: f32,                       // progress of the glide, 1 when it arrived
+    pressure: f32,                    // polyphonic aftertouch
+    unison: [UnisonVoice; MAX_UNISON],
+    unison_count: usize,
+    envelope_time: f64, // seconds on the envelope clock, which the lfos can speed up or slow down
+    noise_time: f64,    // the same clock for the noise and fm envelopes, a mono retrigger
+    fm_time: f64,       // restarts each of them from its own level
+    levels: [f32; MAX_SPECTRUM], // envelope value of each partial in the last sample
+    release_envelopes: [f32; MAX_SPECTRUM], // envelope value of each partial at note off
+    noise: BandNoise,
+    noise_envelope: f32, // last value, like the levels
+    noise_release: f32,
+    fm_envelope: f32,
+    fm_release: f32,
+    pan_left: f32, // note position without unison spread, used for the noise
+    pan_right: f32,
+    pan_random: f32,        // scaled by the random pan
+    spread_modulated: bool, // the mod matrix moved the pan or the unison stack in the last sample
+    spread_version: u32,    // of the patch values the stack was last spread with
+    morph: f32,             // position between spectrum a and b, updated per sample
+    morph_envelope: f32,    // ramps from 0 to 1 after note on, keeps going after note off
     off: bool,
     sustaining: bool,
+    stolen: bool,    // fading out to make room for a new note
+    steal_fade: f32, // gain of the fade out
+    lfos: [Lfo; MAX_LFOS],
+    lfo_fades: [f32; MAX_LFOS],  // ramp from 0 to 1 after note on
+    lfo_values: [f32; MAX_LFOS], // last output after the fade, per voice or global
+}
+
+// what the lfos and the mod matrix change about one note for the current sample, offsets in the
+// units of the patch
+#[derive(Default)]
+struct Modulation {
+    pitch: f32,         // semitones
+    stretch: f32,       // octaves, positive makes every envelope time longer
+    velocity_gain: f32, // not an offset, the velocity through the modulated curve
+    basepitch: f32,
+    gain: f32, // dB
+    inharmonicity: f32,
+    tilt: f32,
+    odd_even: f32,
+    width: f32,
+    key_pan: f32,
+    random_pan: f32,
+    glide_time: f32,
+    glide_curve: f32,
+    detune: f32,
+    spread: f32,
+    envelope: Envelope,
+    noise_level: f32,
+    noise_center: f32,
+    noise_width: f32,
+    noise_envelope: Envelope,
+    velocity_shape: f32,
+    velocity_brightness: f32,
+    morph: f32,
+    morph_velocity: f32,
+    morph_mod_wheel: f32,
+    morph_envelope: f32,
+    morph_time: f32,
+    fm_index: [f32; MAX_FM_SLOTS],
+    fm_envelope: Envelope,
+    fm_velocity: f32,
+    lfos: [LfoSettings; MAX_LFOS],
+    partials: [(ModTarget, usize, f32); MAX_MOD_SLOTS + 2 * MAX_LFOS], // index into the partials
+    partial_count: usize,
+    steps: Steps,
+    overtone_limit: usize, // overtones from here on are silent
+    fm_routes: [Option<(usize, usize)>; MAX_FM_SLOTS], // source and target where they're modulated
+}
+
+// offsets of the discrete targets, in the normalized range like the rest, they're summed up and
+// stepped once every slot is in
+#[derive(Default)]
+struct Steps {
+    partial_count: f32,
+    velocity_curve: f32,
+    fm_sources: [f32; MAX_FM_SLOTS],
+    fm_targets: [f32; MAX_FM_SLOTS],
+    lfo_shapes: [f32; MAX_LFOS],
+    lfo_overtones: [f32; MAX_LFOS],
+}
+
+// a discrete parameter moved by `offset` in the normalized range, it lands on the nearest step
+fn stepped<P: Param>(param: &P, offset: f32) -> P::Plain {
+    param.preview_plain((param.modulated_normalized_value() + offset).clamp(0.0, 1.0))
+}
+
+// midi controllers and global lfos, the mod sources every note shares
+#[derive(Default)]
+struct Controls {
+    mod_wheel: f32,
+    pressure: f32,   // channel aftertouch
+    pitch_bend: f32, // -1 to 1
+    lfos: [f32; MAX_LFOS],
+}
+
+impl Controls {
+    // none for the sources that differ from note to note, the lfos only count in global mode
+    fn source(&self, source: ModSource, patch: &Patch) -> Option<f32> {
+        match source {
+            ModSource::ModWheel => Some(self.mod_wheel),
+            ModSource::Aftertouch => Some(self.pressure),
+            ModSource::PitchBend => Some(self.pitch_bend),
+            ModSource::Lfo1 => (!patch.lfos[0].per_voice).then_some(self.lfos[0]),
+            ModSource::Lfo2 => (!patch.lfos[1].per_voice).then_some(self.lfos[1]),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct UnisonVoice {
+    detune: f32, // frequency ratio
+    phases: [f32; MAX_SPECTRUM],
+    pan_left: f32,
+    pan_right: f32,
+}
+
+impl Default for UnisonVoice {
+    fn default() -> Self {
+        Self {
+            detune: 1.0,
+            phases: [0.0; MAX_SPECTRUM],
+            pan_left: 1.0,
+            pan_right: 1.0,
+        }
+    }
 }
 
 impl Note {
-    fn get_frequency(&self, basepitch: f32, basenote: u8, tuning: Tuning, pitch_bend: f32) -> f32 {
+    fn get_ratio(&self, basenote: u8, tuning: Tuning) -> f32 {
         let note = self.note as i32 - basenote as i32;
-        (match tuning {
-            Tuning::Equal => 2.0f32.powf(note as f32 / 12.0) * basepitch,
+        match tuning {
+            Tuning::Equal => 2.0f32.powf(note as f32 / 12.0),
             Tuning::Just => {
                 let ratio = match note.rem_euclid(12) {
                     0 => 1.0,
@@ -256,7 +2257,7 @@ impl Note {
                     _ => unreachable!(),
                 };
                 let octave = (note as f32 / 12.0).floor();
-                2.0f32.powf(octave) * basepitch * ratio
+                2.0f32.powf(octave) * ratio
             }
             Tuning::Pythagorean => {
                 let ratio = match note.rem_euclid(12) {
@@ -275,47 +2276,864 @@ impl Note {
                     _ => unreachable!(),
                 };
                 let octave = (note as f32 / 12.0).floor();
-                2.0f32.powf(octave) * basepitch * ratio
+                2.0f32.powf(octave) * ratio
             }
-        }) * 2.0f32.powf(pitch_bend / 12.0)
+        }
+    }
+
+    fn release(&mut self) {
+        self.release_envelopes = self.levels;
+        self.noise_release = self.noise_envelope;
+        self.fm_release = self.fm_envelope;
+        self.off = true;
+        self.envelope_time = 0.0;
+        self.noise_time = 0.0;
+        self.fm_time = 0.0;
+    }
+
+    // softer notes lose `brightness` dB/oct scaled by how far they are from full velocity, so the
+    // gains never go above 1, the fundamental and the undertones stay put
+    fn update_brightness(&mut self, patch: &Patch, brightness: f32, velocity_gain: f32) {
+        let tilt = -brightness.max(0.0) * (1.0 - velocity_gain).clamp(0.0, 1.0);
+        for (i, p) in patch.partials() {
+            self.brightness[i] = db_to_gain(tilt * p.ratio.max(1.0).log2());
+        }
+    }
+
+    // brightness and the noise band follow the smoothed and modulated values, they're checked
+    // every FILTER_INTERVAL samples and only recomputed when those moved
+    fn update_filters(
+        &mut self,
+        freq: f32,
+        sample_rate: f32,
+        patch: &Patch,
+        modulation: &Modulation,
+    ) {
+        if self.filter_countdown > 0 {
+            self.filter_countdown -= 1;
+            return;
+        }
+        self.filter_countdown = FILTER_INTERVAL - 1;
+        let brightness = patch.velocity_brightness + modulation.velocity_brightness;
+        let brightness_from = (brightness, modulation.velocity_gain, patch.ratios_version);
+        if brightness_from != self.brightness_from {
+            self.brightness_from = brightness_from;
+            self.update_brightness(patch, brightness, modulation.velocity_gain);
+        }
+        if patch.noise_level + modulation.noise_level > 0.0 {
+            let band = (
+                freq * (patch.noise_center + modulation.noise_center),
+                (patch.noise_width + modulation.noise_width).max(0.1),
+            );
+            if band != self.band {
+                self.band = band;
+                self.noise.set_band(band.0, band.1, sample_rate);
+            }
+        }
+    }
+
+    // rough level for the voice stealing, the fundamental stands in for the whole note
+    fn loudness(&self) -> f32 {
+        self.velocity_gain * self.levels[0]
+    }
+
+    // mono retrigger, the phases keep running and the attack picks up from the current level
+    fn retrigger(&mut self, velocity: f32, velocity_gain: f32, patch: &Patch) {
+        self.velocity = (velocity * 127.0) as u8;
+        self.velocity_gain = velocity_gain;
+        self.envelope_time = patch.envelope.attack_time(self.levels[0]);
+        self.noise_time = patch.noise_envelope.attack_time(self.noise_envelope);
+        self.fm_time = patch.fm_envelope.attack_time(self.fm_envelope);
+        self.morph_envelope = 0.0;
+        self.off = false;
+        for lfo in self.lfos.iter_mut() {
+            lfo.reset();
+        }
+        self.lfo_fades = [0.0; MAX_LFOS];
+    }
+
+    // placement of the note and its unison copies
+    fn spread_unison(&mut self, key_pan: f32, random_pan: f32, detune: f32, spread: f32) {
+        let pan = (key_pan * (self.note as f32 - 64.0) / 64.0 + random_pan * self.pan_random)
+            .clamp(-1.0, 1.0);
+        (self.pan_left, self.pan_right) = pan_gains(pan);
+        let count = self.unison_count;
+        for (k, voice) in self.unison[..count].iter_mut().enumerate() {
+            // -1 to 1 across the stack, a single voice sits in the middle
+            let position = if count > 1 {
+                2.0 * k as f32 / (count - 1) as f32 - 1.0
+            } else {
+                0.0
+            };
+            voice.detune = 2.0f32.powf(position * detune / 1200.0);
+            (voice.pan_left, voice.pan_right) =
+                pan_gains((pan + position * spread).clamp(-1.0, 1.0));
+        }
     }
 
-    fn calculate_envelope(&self, envelope_time: f32, envelope: &[f32; 4]) -> f32 {
-        if self.off {
-            (self.release_envelope * (1.0 - envelope_time / envelope[3])).max(0.0)
-        } else if envelope_time <= envelope[0] {
-            envelope_time / envelope[0]
-        } else if envelope_time <= envelope[0] + envelope[1] {
-            let s1 = 1.0 - envelope[2];
-            1.0 - s1 * (envelope_time - envelope[0]) / envelope[1]
+    // pitch on the way from the previous key to this one
+    fn glide_ratio(&self, curve: f32) -> f32 {
+        if self.glide >= 1.0 {
+            self.ratio
         } else {
-            envelope[2]
+            self.glide_from * (self.ratio / self.glide_from).powf(segment_curve(self.glide, curve))
         }
     }
 
-    fn calculate_sample(
-        &self,
-        envelope_time: f32,
-        overtones: &[f32; 8],
-        envelope: &[f32; 4],
-    ) -> f32 {
-        let sample = overtones
+    fn advance_envelopes(&mut self, stretch: f32, sample_rate: f32) {
+        let step = (2.0f32.powf(-stretch) / sample_rate) as f64;
+        self.envelope_time += step;
+        self.noise_time += step;
+        self.fm_time += step;
+    }
+
+    fn advance_glide(&mut self, glide_time: f32, sample_rate: f32) {
+        if self.glide < 1.0 {
+            self.glide = (self.glide + 1.0 / (glide_time * sample_rate)).min(1.0);
+        }
+    }
+
+    fn update_morph(
+        &mut self,
+        patch: &Patch,
+        modulation: &Modulation,
+        mod_wheel: f32,
+        sample_rate: f32,
+    ) {
+        let time = patch.morph_time + modulation.morph_time;
+        self.morph_envelope = if time > 0.0 {
+            (self.morph_envelope + 1.0 / (time * sample_rate)).min(1.0)
+        } else {
+            1.0
+        };
+        self.morph = (patch.morph
+            + modulation.morph
+            + (patch.morph_velocity + modulation.morph_velocity) * self.velocity as f32 / 127.0
+            + (patch.morph_mod_wheel + modulation.morph_mod_wheel) * mod_wheel
+            + (patch.morph_envelope + modulation.morph_envelope) * self.morph_envelope)
+            .clamp(0.0, 1.0);
+    }
+
+    fn source(&self, source: ModSource, controls: &Controls) -> f32 {
+        match source {
+            ModSource::Velocity => self.velocity as f32 / 127.0,
+            ModSource::Key => (self.note as f32 - 64.0) / 64.0,
+            ModSource::ModWheel => controls.mod_wheel,
+            ModSource::Aftertouch => self.pressure.max(controls.pressure),
+            ModSource::PitchBend => controls.pitch_bend,
+            ModSource::Envelope => self.levels[0],
+            ModSource::NoiseEnvelope => self.noise_envelope,
+            ModSource::FmEnvelope => self.fm_envelope,
+            ModSource::Lfo1 => self.lfo_values[0],
+            ModSource::Lfo2 => self.lfo_values[1],
+        }
+    }
+
+    // the unison stack is fixed while the note plays, so the mod matrix only gets a say as it
+    // starts, with the envelopes and per voice lfos still at zero
+    fn unison_voices(&self, params: &FuririParams, patch: &Patch, controls: &Controls) -> usize {
+        let offset: f32 = patch
+            .mod_slots()
             .iter()
-            .enumerate()
-            .map(|(i, v)| v * (std::f32::consts::TAU * (1.0 + i as f32) * self.phase).sin())
-            .sum::<f32>();
-        sample * (self.velocity as f32 / 127.0) * self.calculate_envelope(envelope_time, envelope)
+            .filter(|slot| slot.target == ModTarget::UnisonVoices)
+            .map(|slot| slot.depth * self.source(slot.source, controls))
+            .sum();
+        (stepped(&params.unison.voices, offset) as usize).clamp(1, MAX_UNISON)
+    }
+
+    // runs the mod matrix and advances the per voice lfos and the fades, the envelope and lfo
+    // sources are read one sample behind
+    fn modulation(
+        &mut self,
+        params: &FuririParams,
+        patch: &Patch,
+        controls: &Controls,
+        sample_rate: f32,
+    ) -> Modulation {
+        let mut modulation = Modulation::default();
+        for slot in patch.mod_slots() {
+            let offset = slot.depth * self.source(slot.source, controls);
+            match params.mod_target(slot.target, slot.number) {
+                Some(param) => modulation.add(slot, param, offset, patch),
+                None => modulation.add_discrete(slot, offset),
+            }
+        }
+        let steps = &modulation.steps;
+        modulation.overtone_limit = if steps.partial_count != 0.0 {
+            stepped(&params.partial_count, steps.partial_count).count()
+        } else {
+            patch.overtone_limit
+        };
+        for (k, route) in modulation.fm_routes.iter_mut().enumerate() {
+            let (source, target) = (steps.fm_sources[k], steps.fm_targets[k]);
+            if source != 0.0 || target != 0.0 {
+                let slot = &params.fm_slots[k];
+                *route = Some((
+                    stepped(&slot.source, source) as usize - 1,
+                    stepped(&slot.target, target) as usize - 1,
+                ));
+            }
+        }
+        // the noise and fm envelopes take release and curves from the main one, modulated or not,
+        // so the three stay together like they do in the patch
+        modulation.noise_envelope = Envelope {
+            delay: modulation.noise_envelope.delay,
+            attack: modulation.noise_envelope.attack,
+            hold: 0.0,
+            decay: modulation.noise_envelope.decay,
+            sustain: modulation.noise_envelope.sustain,
+            ..modulation.envelope
+        };
+        modulation.fm_envelope = Envelope {
+            delay: modulation.fm_envelope.delay,
+            attack: modulation.fm_envelope.attack,
+            hold: 0.0,
+            decay: modulation.fm_envelope.decay,
+            sustain: modulation.fm_envelope.sustain,
+            ..modulation.envelope
+        };
+
+        // the global lfos are shared by every note, their rate, phase and shape are set in process
+        for (i, lfo) in self.lfos.iter_mut().enumerate() {
+            let settings = modulation.lfo(i, patch, params);
+            let fade = &mut self.lfo_fades[i];
+            *fade = if settings.fade > 0.0 {
+                (*fade + 1.0 / (settings.fade * sample_rate)).min(1.0)
+            } else {
+                1.0
+            };
+            let value = if settings.per_voice {
+                lfo.next(settings.shape, settings.rate / sample_rate, settings.phase)
+            } else {
+                controls.lfos[i]
+            } * *fade;
+            self.lfo_values[i] = value;
+            modulation.pitch += value * settings.pitch;
+            modulation.gain += value * settings.gain;
+            modulation.stretch += value * settings.envelope;
+            // both spectra, so it works the same at any morph position
+            if let Some(overtone) = settings.overtone {
+                let offset = value * settings.overtone_depth;
+                modulation.push(ModTarget::Overtone, overtone, offset);
+                modulation.push(ModTarget::OvertoneB, overtone, offset);
+            }
+        }
+
+        let velocity = self.velocity as f32 / 127.0;
+        modulation.velocity_gain = if modulation.velocity_shape != 0.0
+            || modulation.steps.velocity_curve != 0.0
+        {
+            let shape = params.velocity.shape.value() + modulation.velocity_shape;
+            stepped(&params.velocity.curve, modulation.steps.velocity_curve).apply(velocity, shape)
+        } else {
+            self.velocity_gain
+        };
+
+        // put back once the offsets are gone
+        let spread_modulated = modulation.key_pan != 0.0
+            || modulation.random_pan != 0.0
+            || modulation.detune != 0.0
+            || modulation.spread != 0.0;
+        if spread_modulated || self.spread_modulated || self.spread_version != patch.spread_version
+        {
+            self.spread_unison(
+                patch.key_pan + modulation.key_pan,
+                (patch.random_pan + modulation.random_pan).max(0.0),
+                (patch.detune + modulation.detune).max(0.0),
+                (patch.spread + modulation.spread).max(0.0),
+            );
+            self.spread_modulated = spread_modulated;
+            self.spread_version = patch.spread_version;
+        }
+        modulation
+    }
+
+    fn calculate_sample(
+        &mut self,
+        sine: &SineTable,
+        freq: f32,
+        sample_rate: f32,
+        patch: &Patch,
+        modulation: &Modulation,
+    ) -> (f32, f32) {
+        self.update_filters(freq, sample_rate, patch, modulation);
+        let nyquist = sample_rate / 2.0;
+        let envelope_time = self.envelope_time as f32;
+        let envelope = patch.envelope.offset(&modulation.envelope);
+        // partials that keep the note's decay share it, after note off it scales their own level
+        let shared_level = envelope_level(self.off, envelope_time, &envelope, 1.0, 1.0);
+
+        // phase modulation of each slot and unison voice, the sources are read one sample behind
+        self.fm_envelope = envelope_level(
+            self.off,
+            self.fm_time as f32,
+            &patch.fm_envelope.offset(&modulation.fm_envelope),
+            self.fm_release,
+            1.0,
+        );
+        let mut deviations = [[0.0f32; MAX_FM_SLOTS]; MAX_UNISON];
+        if !patch.fm_slots().is_empty() {
+            let velocity = self.velocity as f32 / 127.0;
+            let fm_velocity = (patch.fm_velocity + modulation.fm_velocity).clamp(0.0, 1.0);
+            let scale = self.fm_envelope * (1.0 - fm_velocity + fm_velocity * velocity);
+            for (values, voice) in deviations.iter_mut().zip(&self.unison[..self.unison_count]) {
+                for (value, slot) in values.iter_mut().zip(patch.fm_slots()) {
+                    let (source, target) = modulation.fm_route(slot);
+                    if source >= patch.overtone_count || target >= patch.overtone_count {
+                        continue;
+                    }
+                    let index = (slot.index + modulation.fm_index[slot.slot]).max(0.0);
+                    *value = scale * index * sine.sin(voice.phases[source]);
+                }
+            }
+        }
+
+        let (mut left, mut right) = (0.0, 0.0);
+        for (i, p) in patch.partials() {
+            let p = modulation.partial(i, p, patch);
+            // overtones past the modulated count keep their phases running but stay silent
+            let level = if (modulation.overtone_limit..patch.overtone_count).contains(&i) {
+                0.0
+            } else if p.decay_scale == 1.0 {
+                if self.off {
+                    self.release_envelopes[i] * shared_level
+                } else {
+                    shared_level
+                }
+            } else {
+                envelope_level(
+                    self.off,
+                    envelope_time,
+                    &envelope,
+                    self.release_envelopes[i],
+                    p.decay_scale,
+                )
+            };
+            self.levels[i] = level;
+            let amplitude = (p.amplitude + (p.amplitude_b - p.amplitude) * self.morph)
+                * level
+                * self.brightness[i];
+            let phase_offset = p.phase + p.phase_delta * self.morph;
+            for (voice, values) in self.unison[..self.unison_count].iter_mut().zip(&deviations) {
+                let partial_freq = p.ratio * freq * voice.detune;
+                let phase = &mut voice.phases[i];
+                *phase = (*phase + partial_freq / sample_rate).fract();
+                let gain = bandlimit_gain(partial_freq, nyquist);
+                if gain <= 0.0 || amplitude == 0.0 {
+                    continue;
+                }
+                let deviation: f32 = patch
+                    .fm_slots()
+                    .iter()
+                    .zip(values)
+                    .filter(|(slot, _)| modulation.fm_route(slot).1 == i)
+                    .map(|(_, value)| value)
+                    .sum();
+                let sample = amplitude * gain * sine.sin(*phase + phase_offset + deviation);
+                left += sample * p.pan_left * voice.pan_left;
+                right += sample * p.pan_right * voice.pan_right;
+            }
+        }
+        // uncorrelated copies add up in power, keep the overall level steady
+        let unison_scale = 1.0 / (self.unison_count as f32).sqrt();
+        left *= unison_scale;
+        right *= unison_scale;
+
+        self.noise_envelope = envelope_level(
+            self.off,
+            self.noise_time as f32,
+            &patch.noise_envelope.offset(&modulation.noise_envelope),
+            self.noise_release,
+            1.0,
+        );
+        let noise_level = patch.noise_level + modulation.noise_level;
+        if noise_level > 0.0 {
+            let sample = noise_level * self.noise_envelope * self.noise.next();
+            left += sample * self.pan_left;
+            right += sample * self.pan_right;
+        }
+
+        if self.stolen {
+            self.steal_fade = (self.steal_fade - 1.0 / (STEAL_FADE * sample_rate)).max(0.0);
+        }
+        let scale = modulation.velocity_gain * self.steal_fade;
+        (left * scale, right * scale)
+    }
+
+    // done once it's released and every envelope has run out
+    fn finished(&self, patch: &Patch) -> bool {
+        self.off
+            && self.noise_envelope <= 0.0
+            && patch.partials().all(|(i, _)| self.levels[i] <= 0.0)
+    }
+}
+
+impl Modulation {
+    // one slot of the mod matrix, `offset` is in the normalized range of the target
+    fn add(&mut self, slot: &ModSlot, param: &FloatParam, offset: f32, patch: &Patch) {
+        let value = param.value();
+        let modulated =
+            param.preview_plain((param.modulated_normalized_value() + offset).clamp(0.0, 1.0));
+        let delta = modulated - value;
+        let number = slot.number;
+        match slot.target {
+            ModTarget::BasePitch => self.basepitch += delta,
+            ModTarget::Gain => self.gain += delta,
+            ModTarget::Inharmonicity => self.inharmonicity += delta,
+            ModTarget::Tilt => self.tilt += delta,
+            ModTarget::OddEven => self.odd_even += delta,
+            ModTarget::Width => self.width += delta,
+            ModTarget::KeyPan => self.key_pan += delta,
+            ModTarget::RandomPan => self.random_pan += delta,
+            ModTarget::Glide => self.glide_time += delta / 1000.0,
+            ModTarget::GlideCurve => self.glide_curve += delta,
+            ModTarget::Detune => self.detune += delta,
+            ModTarget::Spread => self.spread += delta,
+            ModTarget::Overtone
+            | ModTarget::OvertoneB
+            | ModTarget::Ratio
+            | ModTarget::DecayScale
+            | ModTarget::Pan => {
+                if number < patch.overtone_count {
+                    self.push(slot.target, number, delta);
+                }
+            }
+            ModTarget::Phase | ModTarget::PhaseB => {
+                if number < patch.overtone_count {
+                    self.push(slot.target, number, delta / 360.0);
+                }
+            }
+            ModTarget::Undertone => self.push(ModTarget::Undertone, MAX_PARTIALS + number, delta),
+            // in seconds even when the times are synced
+            ModTarget::Delay => self.envelope.delay += delta / 1000.0,
+            ModTarget::Attack => self.envelope.attack += delta / 1000.0,
+            ModTarget::Hold => self.envelope.hold += delta / 1000.0,
+            ModTarget::Decay => self.envelope.decay += delta / 1000.0,
+            ModTarget::Sustain => self.envelope.sustain += delta,
+            ModTarget::Release => self.envelope.release += delta / 1000.0,
+            ModTarget::AttackCurve => self.envelope.attack_curve += delta,
+            ModTarget::DecayCurve => self.envelope.decay_curve += delta,
+            ModTarget::ReleaseCurve => self.envelope.release_curve += delta,
+            ModTarget::NoiseLevel => self.noise_level += delta,
+            ModTarget::NoiseCenter => self.noise_center += delta,
+            ModTarget::NoiseWidth => self.noise_width += delta,
+            ModTarget::NoiseAttack => self.noise_envelope.attack += delta / 1000.0,
+            ModTarget::NoiseDecay => self.noise_envelope.decay += delta / 1000.0,
+            ModTarget::NoiseSustain => self.noise_envelope.sustain += delta,
+            ModTarget::VelocityShape => self.velocity_shape += delta,
+            ModTarget::VelocityBrightness => self.velocity_brightness += delta,
+            ModTarget::Morph => self.morph += delta,
+            ModTarget::MorphVelocity => self.morph_velocity += delta,
+            ModTarget::MorphModWheel => self.morph_mod_wheel += delta,
+            ModTarget::MorphEnvelope => self.morph_envelope += delta,
+            ModTarget::MorphTime => self.morph_time += delta / 1000.0,
+            ModTarget::FmIndex => self.fm_index[number] += delta / TAU,
+            ModTarget::FmAttack => self.fm_envelope.attack += delta / 1000.0,
+            ModTarget::FmDecay => self.fm_envelope.decay += delta / 1000.0,
+            ModTarget::FmSustain => self.fm_envelope.sustain += delta,
+            ModTarget::FmVelocity => self.fm_velocity += delta,
+            // the rate follows whichever of the two is in use
+            ModTarget::LfoRate => {
+                if !patch.lfos[number].synced {
+                    self.lfos[number].rate += delta;
+                }
+            }
+            ModTarget::LfoBeats => {
+                if patch.lfos[number].synced {
+                    self.lfos[number].rate += patch.lfos[number].rate * (value / modulated - 1.0);
+                }
+            }
+            ModTarget::LfoPhase => self.lfos[number].phase += delta / 360.0,
+            ModTarget::LfoFade => self.lfos[number].fade += delta / 1000.0,
+            ModTarget::LfoPitch => self.lfos[number].pitch += delta,
+            ModTarget::LfoGain => self.lfos[number].gain += delta,
+            ModTarget::LfoOvertoneDepth => self.lfos[number].overtone_depth += delta,
+            ModTarget::LfoEnvelope => self.lfos[number].envelope += delta,
+            ModTarget::PartialCount
+            | ModTarget::UnisonVoices
+            | ModTarget::VelocityCurve
+            | ModTarget::FmSource
+            | ModTarget::FmTarget
+            | ModTarget::LfoShape
+            | ModTarget::LfoOvertone => {}
+        }
+    }
+
+    // the discrete targets only gather their offsets here, see `Steps`
+    fn add_discrete(&mut self, slot: &ModSlot, offset: f32) {
+        let number = slot.number;
+        let steps = &mut self.steps;
+        match slot.target {
+            ModTarget::PartialCount => steps.partial_count += offset,
+            ModTarget::VelocityCurve => steps.velocity_curve += offset,
+            ModTarget::FmSource => steps.fm_sources[number] += offset,
+            ModTarget::FmTarget => steps.fm_targets[number] += offset,
+            ModTarget::LfoShape => steps.lfo_shapes[number] += offset,
+            ModTarget::LfoOvertone => steps.lfo_overtones[number] += offset,
+            // the unison stack is set up as the note starts, see `Note::unison_voices`
+            _ => {}
+        }
+    }
+
+    // the settings of lfo `i` with the mod matrix applied
+    fn lfo(&self, i: usize, patch: &Patch, params: &FuririParams) -> LfoSettings {
+        let mut settings = patch.lfos[i].offset(&self.lfos[i]);
+        let lfo = &params.lfos[i];
+        if self.steps.lfo_shapes[i] != 0.0 {
+            settings.shape = stepped(&lfo.shape, self.steps.lfo_shapes[i]);
+        }
+        if self.steps.lfo_overtones[i] != 0.0 {
+            settings.overtone = (stepped(&lfo.overtone, self.steps.lfo_overtones[i]) as usize)
+                .checked_sub(1)
+                .filter(|&overtone| overtone < patch.overtone_count);
+        }
+        settings
+    }
+
+    // source and target of an fm slot for this note
+    fn fm_route(&self, slot: &FmSlot) -> (usize, usize) {
+        self.fm_routes[slot.slot].unwrap_or((slot.source, slot.target))
+    }
+
+    fn push(&mut self, target: ModTarget, partial: usize, offset: f32) {
+        self.partials[self.partial_count] = (target, partial, offset);
+        self.partial_count += 1;
+    }
+
+    // a partial as the note plays it, with tilt and balance applied to the amplitudes
+    fn partial(&self, i: usize, p: &Partial, patch: &Patch) -> Partial {
+        let mut p = *p;
+        let (mut ratio, mut pan) = (0.0, 0.0);
+        for &(target, partial, offset) in &self.partials[..self.partial_count] {
+            if partial != i {
+                continue;
+            }
+            match target {
+                ModTarget::Overtone => p.amplitude += offset,
+                ModTarget::OvertoneB => p.amplitude_b += offset,
+                ModTarget::Undertone => {
+                    p.amplitude += offset;
+                    p.amplitude_b += offset;
+                }
+                ModTarget::Phase => {
+                    p.phase += offset;
+                    p.phase_delta -= offset;
+                }
+                ModTarget::PhaseB => p.phase_delta += offset,
+                ModTarget::Ratio => ratio += offset,
+                ModTarget::DecayScale => p.decay_scale = (p.decay_scale + offset).max(0.0),
+                ModTarget::Pan => pan += offset,
+                _ => {}
+            }
+        }
+        p.phase_delta = wrap_phase(p.phase_delta);
+        if pan != 0.0 {
+            // the balance law keeps one side at 1, so the pan is the difference
+            (p.pan_left, p.pan_right) =
+                pan_gains((p.pan_right - p.pan_left + pan).clamp(-1.0, 1.0));
+        }
+        if i >= patch.overtone_count {
+            return p;
+        }
+
+        let harmonic = (i + 1) as f32;
+        if ratio != 0.0 || self.inharmonicity != 0.0 {
+            // auto follows the stretched series and the offset moves it from there
+            let auto = patch.overtone_states[i].ratio <= 0.0;
+            let base = if auto && self.inharmonicity != 0.0 {
+                let inharmonicity = (patch.inharmonicity + self.inharmonicity).max(0.0);
+                harmonic * (1.0 + inharmonicity * harmonic * harmonic).sqrt()
+            } else {
+                p.ratio
+            };
+            p.ratio = (base + ratio).max(0.0);
+        }
+        let tilt_gain = if self.tilt != 0.0 {
+            db_to_gain((patch.tilt + self.tilt) * harmonic.log2())
+        } else {
+            patch.tilt_gains[i]
+        };
+        let gain = tilt_gain * balance(i + 1, (patch.odd_even + self.odd_even).clamp(-1.0, 1.0));
+        p.amplitude *= gain;
+        p.amplitude_b *= gain;
+        p
+    }
+}
+
+impl LfoSettings {
+    // with the offsets of the mod matrix
+    fn offset(&self, by: &LfoSettings) -> Self {
+        Self {
+            rate: (self.rate + by.rate).max(0.0),
+            phase: (self.phase + by.phase).rem_euclid(1.0),
+            fade: (self.fade + by.fade).max(0.0),
+            pitch: self.pitch + by.pitch,
+            gain: self.gain + by.gain,
+            overtone_depth: self.overtone_depth + by.overtone_depth,
+            envelope: self.envelope + by.envelope,
+            ..*self
+        }
     }
 }
 
 impl Default for Furiri {
     fn default() -> Self {
+        let params = Arc::new(FuririParams::default());
+        let patch = Patch::new(&params, None, 1.0);
         Self {
-            params: Arc::new(FuririParams::default()),
-            current_notes: Vec::with_capacity(MAX_VOICES),
+            params,
+            current_notes: Vec::with_capacity(MAX_NOTES),
             sample_rate: 1.0,
-            pitch_bend: 0.0,
+            pitch_bend_ratio: 1.0,
+            held_notes: Vec::with_capacity(128),
             sustain_pedal: false,
+            controls: Controls::default(),
+            notes_started: 0,
+            clock: 0.0,
+            lfos: std::array::from_fn(|i| Lfo::new(0x4c46_4f31 + i as u32)),
+            patch,
+            sine: SineTable::new(),
+            rng: Rng::new(0x4675_7269),
+        }
+    }
+}
+
+impl Furiri {
+    // the shared lfos only follow the sources every note has in common
+    fn global_lfo_modulation(&self, patch: &Patch) -> Modulation {
+        let mut modulation = Modulation::default();
+        for slot in patch.mod_slots().iter().filter(|slot| {
+            matches!(
+                slot.target,
+                ModTarget::LfoRate
+                    | ModTarget::LfoBeats
+                    | ModTarget::LfoPhase
+                    | ModTarget::LfoShape
+            )
+        }) {
+            let Some(value) = self.controls.source(slot.source, patch) else {
+                continue;
+            };
+            let offset = slot.depth * value;
+            match self.params.mod_target(slot.target, slot.number) {
+                Some(param) => modulation.add(slot, param, offset, patch),
+                None => modulation.add_discrete(slot, offset),
+            }
+        }
+        modulation
+    }
+
+    fn velocity_gain(&self, velocity: f32) -> f32 {
+        let curve = self.params.velocity.curve.value();
+        curve.apply(velocity, self.params.velocity.shape.value())
+    }
+
+    fn note_off(&mut self, note: u8) {
+        let notes = self
+            .current_notes
+            .iter_mut()
+            .filter(|n| n.note == note && !n.off);
+        if self.sustain_pedal {
+            for n in notes {
+                n.sustaining = true;
+            }
+        } else {
+            for n in notes {
+                n.release();
+            }
+        }
+    }
+
+    // the held key with the highest priority
+    fn priority_note(&self) -> Option<u8> {
+        match self.params.note_priority.value() {
+            NotePriority::Last => self.held_notes.last().copied(),
+            NotePriority::Low => self.held_notes.iter().min().copied(),
+            NotePriority::High => self.held_notes.iter().max().copied(),
+        }
+    }
+
+    // the newest note that hasn't been released or stolen
+    fn sounding_note(&self) -> Option<usize> {
+        self.current_notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.off && !n.stolen)
+            .max_by_key(|(_, n)| n.started)
+            .map(|(i, _)| i)
+    }
+
+    fn mono_note_on(&mut self, note: u8, velocity: f32, patch: &Patch) {
+        // a held key with a higher priority keeps playing
+        if self.priority_note() != Some(note) {
+            return;
+        }
+        let retrigger = self.params.voice_mode.value() == VoiceMode::Mono;
+        match self.sounding_note() {
+            Some(index) => self.glide_to(index, note, retrigger.then_some(velocity), patch),
+            None => self.note_on(note, velocity, patch),
+        }
+    }
+
+    // goes back to the next held key, false when the note should be released normally
+    fn mono_note_off(&mut self, note: u8, patch: &Patch) -> bool {
+        let (Some(target), Some(index)) = (self.priority_note(), self.sounding_note()) else {
+            return false;
+        };
+        if self.current_notes[index].note == note {
+            let retrigger = self.params.voice_mode.value() == VoiceMode::Mono;
+            let velocity = self.current_notes[index].velocity as f32 / 127.0;
+            self.glide_to(index, target, retrigger.then_some(velocity), patch);
+        }
+        true
+    }
+
+    fn glide_to(&mut self, index: usize, note: u8, retrigger: Option<f32>, patch: &Patch) {
+        let basenote = self.params.basenote.value() as u8;
+        let tuning = self.params.tuning.value();
+        let velocity_gain = retrigger.map(|velocity| self.velocity_gain(velocity));
+        let current = &mut self.current_notes[index];
+        if current.note != note {
+            current.glide_from = current.glide_ratio(patch.glide_curve);
+            current.glide = if patch.glide_time > 0.0 { 0.0 } else { 1.0 };
+            current.note = note;
+            current.ratio = current.get_ratio(basenote, tuning);
+        }
+        if let (Some(velocity), Some(velocity_gain)) = (retrigger, velocity_gain) {
+            current.retrigger(velocity, velocity_gain, patch);
+        }
+        current.sustaining = false;
+    }
+
+    fn note_on(&mut self, note: u8, velocity: f32, patch: &Patch) {
+        self.notes_started += 1;
+        let mut new_note = Note {
+            started: self.notes_started,
+            note,
+            velocity: (velocity * 127.0) as u8,
+            velocity_gain: self.velocity_gain(velocity),
+            brightness: [1.0; MAX_SPECTRUM],
+            brightness_from: (f32::NAN, f32::NAN, 0),
+            band: (f32::NAN, f32::NAN),
+            filter_countdown: 0,
+            ratio: 1.0,
+            glide_from: 1.0,
+            glide: 1.0,
+            pressure: 0.0,
+            unison: [UnisonVoice::default(); MAX_UNISON],
+            unison_count: 1,
+            envelope_time: 0.0,
+            noise_time: 0.0,
+            fm_time: 0.0,
+            levels: [0.0; MAX_SPECTRUM],
+            release_envelopes: [0.0; MAX_SPECTRUM],
+            noise: BandNoise::new(self.rng.next_u32()),
+            noise_envelope: 0.0,
+            noise_release: 0.0,
+            fm_envelope: 0.0,
+            fm_release: 0.0,
+            pan_left: 1.0,
+            pan_right: 1.0,
+            pan_random: self.rng.next_bipolar(),
+            spread_modulated: false,
+            spread_version: patch.spread_version,
+            morph: 0.0,
+            morph_envelope: 0.0,
+            off: false,
+            sustaining: false,
+            stolen: false,
+            steal_fade: 1.0,
+            lfos: std::array::from_fn(|_| Lfo::new(self.rng.next_u32())),
+            lfo_fades: [0.0; MAX_LFOS],
+            lfo_values: [0.0; MAX_LFOS],
+        };
+        let unison_count = new_note.unison_voices(&self.params, patch, &self.controls);
+        new_note.unison_count = unison_count;
+
+        let polyphony = patch.polyphony.max(unison_count);
+        // every unison copy counts as a voice
+        let mut voices: usize = self
+            .current_notes
+            .iter()
+            .filter(|n| !n.stolen)
+            .map(|n| n.unison_count)
+            .sum();
+        while voices + unison_count > polyphony {
+            let Some(index) = self.steal_candidate(note, patch) else {
+                break;
+            };
+            let stolen = &mut self.current_notes[index];
+            stolen.stolen = true;
+            voices -= stolen.unison_count;
+        }
+        // fading notes can pile up on dense chords, make room without allocating, the stolen note
+        // that is furthest into its fade goes first and a sounding note is only cut as a last
+        // resort, which can't happen while the polyphony is below half of MAX_NOTES
+        if self.current_notes.len() == MAX_NOTES {
+            let faded = self
+                .current_notes
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| n.stolen)
+                .min_by(|(_, a), (_, b)| a.steal_fade.total_cmp(&b.steal_fade))
+                .map(|(i, _)| i);
+            if let Some(index) = faded.or_else(|| self.steal_candidate(note, patch)) {
+                self.current_notes.swap_remove(index);
+            }
+        }
+
+        new_note.ratio = new_note.get_ratio(
+            self.params.basenote.value() as u8,
+            self.params.tuning.value(),
+        );
+        new_note.spread_unison(patch.key_pan, patch.random_pan, patch.detune, patch.spread);
+
+        let freq = patch.basepitch * new_note.ratio * self.pitch_bend_ratio;
+        let random_unison = unison_count > 1 && self.params.unison.random_phase.value();
+        let phase_mode = self.params.phase_mode.value();
+        let partial_random_phase = self.params.partial_random_phase.value();
+        for voice in new_note.unison[..unison_count].iter_mut() {
+            // where the voice starts, in cycles of its fundamental
+            let start = match phase_mode {
+                PhaseMode::Reset if !random_unison => 0.0,
+                PhaseMode::Reset | PhaseMode::Random => self.rng.next_f32() as f64,
+                PhaseMode::Free => self.clock * (freq * voice.detune) as f64,
+            };
+            // every slot, so overtones that come in later start in place too
+            for (phase, p) in voice.phases.iter_mut().zip(&patch.overtones) {
+                *phase = if partial_random_phase {
+                    self.rng.next_f32()
+                } else {
+                    (p.ratio as f64 * start).fract() as f32
+                };
+            }
+        }
+
+        self.current_notes.push(new_note);
+    }
+
+    fn steal_candidate(&self, note: u8, patch: &Patch) -> Option<usize> {
+        let candidates = self
+            .current_notes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| !n.stolen);
+        let oldest = |filter: &dyn Fn(&Note) -> bool| {
+            candidates
+                .clone()
+                .filter(|(_, n)| filter(n))
+                .min_by_key(|(_, n)| n.started)
+                .map(|(i, _)| i)
+        };
+        match patch.steal_policy {
+            StealPolicy::Oldest => oldest(&|_| true),
+            StealPolicy::Quietest => candidates
+                .clone()
+                .map(|(i, n)| (i, n.loudness()))
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(i, _)| i),
+            StealPolicy::Released => oldest(&|n| n.off).or_else(|| oldest(&|_| true)),
+            StealPolicy::SameNote => oldest(&|n| n.note == note).or_else(|| oldest(&|_| true)),
         }
     }
 }
@@ -364,6 +3182,7 @@ impl Plugin for Furiri {
         _context: &mut impl InitContext<Self>,
     ) -> bool {
         self.sample_rate = buffer_config.sample_rate;
+        self.patch = Patch::new(&self.params, None, self.sample_rate);
         true
     }
 
@@ -373,110 +3192,150 @@ impl Plugin for Furiri {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        let overtones: [f32; 8] = [
-            self.params.overtones.overtone1.value(),
-            self.params.overtones.overtone2.value(),
-            self.params.overtones.overtone3.value(),
-            self.params.overtones.overtone4.value(),
-            self.params.overtones.overtone5.value(),
-            self.params.overtones.overtone6.value(),
-            self.params.overtones.overtone7.value(),
-            self.params.overtones.overtone8.value(),
-        ];
-        let envelope: [f32; 4] = [
-            self.params.envelope.attack.value() / 1000.0,
-            self.params.envelope.decay.value() / 1000.0,
-            self.params.envelope.sustain.value(),
-            self.params.envelope.release.value() / 1000.0,
-        ];
+        let tempo = context.transport().tempo;
+        let mut patch = self.patch;
+        let basenote = self.params.basenote.value() as u8;
+
+        for note in self.current_notes.iter_mut() {
+            note.ratio = note.get_ratio(basenote, self.params.tuning.value());
+        }
 
         let mut next_event = context.next_event();
 
         for (sample_id, channel_samples) in buffer.iter_samples().enumerate() {
+            patch.update(&self.params, tempo, smoothed);
+
             while let Some(event) = next_event {
                 if event.timing() > sample_id as u32 {
                     break;
                 }
                 match event {
                     NoteEvent::NoteOn { note, velocity, .. } => {
-                        if self.current_notes.len() >= MAX_VOICES {
-                            self.current_notes.swap_remove(0);
+                        self.held_notes.retain(|&n| n != note);
+                        self.held_notes.push(note);
+                        if self.params.voice_mode.value() == VoiceMode::Poly {
+                            self.note_on(note, velocity, &patch);
+                        } else {
+                            self.mono_note_on(note, velocity, &patch);
                         }
-                        self.current_notes.push(Note {
-                            note,
-                            velocity: (velocity * 127.0) as u8,
-                            phase: 0.0,
-                            samples_since_event: 0,
-                            release_envelope: 0.0,
-                            off: false,
-                            sustaining: false,
-                        });
                     }
                     NoteEvent::NoteOff { note, .. } => {
-                        if self.sustain_pedal {
-                            for n in self.current_notes.iter_mut().filter(|n| n.note == note) {
-                                n.sustaining = true;
-                            }
-                        } else {
-                            for n in self.current_notes.iter_mut().filter(|n| n.note == note) {
-                                n.release_envelope = n.calculate_envelope(
-                                    n.samples_since_event as f32 / self.sample_rate,
-                                    &envelope,
-                                );
-                                n.off = true;
-                                n.samples_since_event = 0;
-                            }
+                        self.held_notes.retain(|&n| n != note);
+                        if self.params.voice_mode.value() == VoiceMode::Poly
+                            || !self.mono_note_off(note, &patch)
+                        {
+                            self.note_off(note);
                         }
                     }
                     NoteEvent::MidiPitchBend { value, .. } => {
-                        self.pitch_bend = PITCH_RANGE * 2.0 * (value - 0.5);
+                        let pitch_bend = PITCH_RANGE * 2.0 * (value - 0.5);
+                        self.pitch_bend_ratio = 2.0f32.powf(pitch_bend / 12.0);
+                        self.controls.pitch_bend = 2.0 * (value - 0.5);
                     }
                     NoteEvent::MidiCC { cc, value, .. } => {
-                        if cc == 64 {
+                        if cc == 1 {
+                            self.controls.mod_wheel = value;
+                        } else if cc == 64 {
                             self.sustain_pedal = value > 0.5;
                             if !self.sustain_pedal {
                                 for n in self.current_notes.iter_mut().filter(|n| n.sustaining) {
-                                    n.release_envelope = n.calculate_envelope(
-                                        n.samples_since_event as f32 / self.sample_rate,
-                                        &envelope,
-                                    );
-                                    n.off = true;
-                                    n.samples_since_event = 0;
+                                    n.release();
                                 }
                             }
                         }
                     }
+                    NoteEvent::PolyPressure { note, pressure, .. } => {
+                        for n in self
+                            .current_notes
+                            .iter_mut()
+                            .filter(|n| n.note == note && !n.off)
+                        {
+                            n.pressure = pressure;
+                        }
+                    }
+                    NoteEvent::MidiChannelPressure { pressure, .. } => {
+                        self.controls.pressure = pressure;
+                    }
                     _ => {}
                 }
                 next_event = context.next_event();
             }
 
-            let val = self
+            let modulation = self.global_lfo_modulation(&patch);
+            for (i, (value, lfo)) in self
+                .controls
+                .lfos
+                .iter_mut()
+                .zip(&mut self.lfos)
+                .enumerate()
+            {
+                let settings = modulation.lfo(i, &patch, &self.params);
+                *value = lfo.next(
+                    settings.shape,
+                    settings.rate / self.sample_rate,
+                    settings.phase,
+                );
+            }
+
+            let (mid, side) = self
                 .current_notes
                 .iter_mut()
                 .map(|note| {
-                    let freq = note.get_frequency(
-                        self.params.basepitch.value(),
-                        self.params.basenote.value() as u8,
-                        self.params.tuning.value(),
-                        self.pitch_bend,
+                    let modulation =
+                        note.modulation(&self.params, &patch, &self.controls, self.sample_rate);
+                    let freq = (patch.basepitch + modulation.basepitch).max(0.0)
+                        * note.glide_ratio(patch.glide_curve + modulation.glide_curve)
+                        * self.pitch_bend_ratio
+                        * 2.0f32.powf(modulation.pitch / 12.0);
+                    note.advance_glide(
+                        (patch.glide_time + modulation.glide_time).max(0.0),
+                        self.sample_rate,
                     );
-                    note.phase = (note.phase + freq / self.sample_rate).fract();
-
-                    note.samples_since_event += 1;
-                    let envelope_time = note.samples_since_event as f32 / self.sample_rate;
-                    note.calculate_sample(envelope_time, &overtones, &envelope)
+                    note.advance_envelopes(modulation.stretch, self.sample_rate);
+                    note.update_morph(
+                        &patch,
+                        &modulation,
+                        self.controls.mod_wheel,
+                        self.sample_rate,
+                    );
+                    let (left, right) = note.calculate_sample(
+                        &self.sine,
+                        freq,
+                        self.sample_rate,
+                        &patch,
+                        &modulation,
+                    );
+                    // width is modulated per note, so each note goes to mid/side on its own
+                    let gain = db_to_gain_fast(modulation.gain);
+                    let width = (patch.width + modulation.width).max(0.0) / 100.0;
+                    (
+                        gain * (left + right) / 2.0,
+                        gain * (left - right) / 2.0 * width,
+                    )
                 })
-                .sum::<f32>();
+                .fold((0.0, 0.0), |(m, s), (nm, ns)| (m + nm, s + ns));
 
-            for sample in channel_samples {
-                *sample = val * db_to_gain_fast(self.params.gain.value());
+            let gain = db_to_gain_fast(patch.gain);
+            let channel_count = channel_samples.len();
+            for (channel, sample) in channel_samples.into_iter().enumerate() {
+                *sample = gain
+                    * match (channel_count, channel) {
+                        (1, _) => mid,
+                        (_, 0) => mid + side,
+                        _ => mid - side,
+                    };
             }
+            self.clock += 1.0 / self.sample_rate as f64;
         }
 
         self.current_notes.retain(|n| {
-            !n.off || (n.samples_since_event as f32 / self.sample_rate < envelope[3])
+            if n.stolen {
+                n.steal_fade > 0.0
+            } else {
+                !n.finished(&patch)
+            }
         });
+        self.patch = patch;
 
         ProcessStatus::KeepAlive
     }
@@ -506,3 +3365,416 @@ impl Vst3Plugin for Furiri {
 
 nih_export_clap!(Furiri);
 nih_export_vst3!(Furiri);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balance_keeps_both_at_zero() {
+        assert_eq!(balance(1, 0.0), 1.0);
+        assert_eq!(balance(2, 0.0), 1.0);
+    }
+
+    #[test]
+    fn balance_removes_one_side() {
+        assert_eq!(balance(3, 1.0), 0.0);
+        assert_eq!(balance(4, 1.0), 1.0);
+        assert_eq!(balance(3, -1.0), 1.0);
+        assert_eq!(balance(4, -1.0), 0.0);
+        assert_eq!(balance(3, 0.5), 0.5);
+    }
+
+    #[test]
+    fn balance_keeps_the_fundamental() {
+        assert_eq!(balance(1, 1.0), 1.0);
+        assert_eq!(balance(1, -1.0), 1.0);
+    }
+
+    #[test]
+    fn phases_smooth_the_short_way_around() {
+        let mut smoother = PhaseSmoother::default();
+        assert!(smoother.next(350.0 / 360.0, 0));
+        assert_eq!(smoother.value, 350.0 / 360.0);
+        for _ in 0..4 {
+            assert!(smoother.next(10.0 / 360.0, 4));
+            assert!(wrap_phase(smoother.value).abs() <= 10.0 / 360.0 + 1e-6);
+        }
+        assert_eq!(smoother.value, 10.0 / 360.0);
+        assert!(!smoother.next(10.0 / 360.0, 4));
+    }
+
+    #[test]
+    fn ratio_offsets_move_auto_partials_from_the_series() {
+        let params = FuririParams::default();
+        let patch = Patch::new(&params, None, 48000.0);
+        let mut modulation = Modulation::default();
+        modulation.push(ModTarget::Ratio, 1, 0.01);
+        let p = modulation.partial(1, &patch.overtones[1], &patch);
+        assert!((p.ratio - 2.01).abs() < 1e-6, "{}", p.ratio);
+    }
+
+    #[test]
+    fn pan_offsets_start_from_the_partial_pan() {
+        let params = FuririParams::default();
+        let mut patch = Patch::new(&params, None, 48000.0);
+        (patch.overtones[0].pan_left, patch.overtones[0].pan_right) = pan_gains(-0.5);
+        let mut modulation = Modulation::default();
+        modulation.push(ModTarget::Pan, 0, 1.0);
+        let p = modulation.partial(0, &patch.overtones[0], &patch);
+        assert_eq!((p.pan_left, p.pan_right), pan_gains(0.5));
+        modulation.push(ModTarget::Pan, 0, 1.0);
+        let p = modulation.partial(0, &patch.overtones[0], &patch);
+        assert_eq!((p.pan_left, p.pan_right), pan_gains(1.0));
+    }
+
+    #[test]
+    fn stepped_moves_discrete_parameters_by_whole_steps() {
+        let params = FuririParams::default();
+        assert!(stepped(&params.partial_count, 0.0) == PartialCount::Eight);
+        assert!(stepped(&params.partial_count, 0.4) == PartialCount::Sixteen);
+        assert!(stepped(&params.partial_count, 1.0) == PartialCount::SixtyFour);
+        assert!(stepped(&params.partial_count, -1.0) == PartialCount::Eight);
+        assert_eq!(stepped(&params.unison.voices, 0.0), 1);
+        assert_eq!(stepped(&params.unison.voices, 1.0), MAX_UNISON as i32);
+    }
+
+    fn mod_slot(source: ModSource, target: ModTarget, depth: f32) -> ModSlot {
+        ModSlot {
+            source,
+            target,
+            number: 0,
+            depth,
+        }
+    }
+
+    #[test]
+    fn lfo_beats_scale_the_synced_rate() {
+        let params = FuririParams::default();
+        let mut patch = Patch::new(&params, None, 48000.0);
+        patch.lfos[0].synced = true;
+        patch.lfos[0].rate = 2.0;
+        let beats = &params.lfos[0].beats;
+        let mut modulation = Modulation::default();
+        let slot = mod_slot(ModSource::ModWheel, ModTarget::LfoBeats, 1.0);
+        modulation.add(&slot, beats, 0.2, &patch);
+        // the rate only follows the beats while synced
+        let slot = mod_slot(ModSource::ModWheel, ModTarget::LfoRate, 1.0);
+        modulation.add(&slot, &params.lfos[0].rate, 0.2, &patch);
+        let longer = beats.preview_plain(beats.modulated_normalized_value() + 0.2);
+        let rate = modulation.lfo(0, &patch, &params).rate;
+        assert!((rate - 2.0 * beats.value() / longer).abs() < 1e-5, "{rate}");
+    }
+
+    #[test]
+    fn global_lfos_follow_only_the_shared_sources() {
+        let mut synth = Furiri::default();
+        let mut patch = synth.patch;
+        patch.mod_slots[0] = mod_slot(ModSource::ModWheel, ModTarget::LfoRate, 1.0);
+        patch.mod_slots[1] = mod_slot(ModSource::Velocity, ModTarget::LfoPhase, 1.0);
+        patch.mod_slot_count = 2;
+        let settings = synth
+            .global_lfo_modulation(&patch)
+            .lfo(0, &patch, &synth.params);
+        assert!((settings.rate - patch.lfos[0].rate).abs() < 1e-3);
+        synth.controls.mod_wheel = 1.0;
+        let settings = synth
+            .global_lfo_modulation(&patch)
+            .lfo(0, &patch, &synth.params);
+        let fastest = synth.params.lfos[0].rate.preview_plain(1.0);
+        assert!((settings.rate - fastest).abs() < 1e-3, "{}", settings.rate);
+        // velocity differs from note to note, so the shared phase stays put
+        assert_eq!(settings.phase, patch.lfos[0].phase);
+    }
+
+    #[test]
+    fn controls_pass_on_only_global_lfos() {
+        let params = FuririParams::default();
+        let mut patch = Patch::new(&params, None, 48000.0);
+        let controls = Controls {
+            lfos: [0.25, 0.5],
+            ..Controls::default()
+        };
+        patch.lfos[1].per_voice = false;
+        assert_eq!(controls.source(ModSource::Lfo2, &patch), Some(0.5));
+        patch.lfos[1].per_voice = true;
+        assert_eq!(controls.source(ModSource::Lfo2, &patch), None);
+        assert_eq!(controls.source(ModSource::Velocity, &patch), None);
+    }
+
+    #[test]
+    fn pan_gains_follow_the_balance_law() {
+        assert_eq!(pan_gains(0.0), (1.0, 1.0));
+        assert_eq!(pan_gains(-1.0), (1.0, 0.0));
+        assert_eq!(pan_gains(1.0), (0.0, 1.0));
+        assert_eq!(pan_gains(0.5), (0.5, 1.0));
+    }
+
+    #[test]
+    fn segment_curves_start_and_end_in_place() {
+        for curvature in [-1.0, -0.5, 0.0, 0.5, 1.0] {
+            assert!(segment_curve(0.0, curvature).abs() < 1e-6);
+            assert!((segment_curve(1.0, curvature) - 1.0).abs() < 1e-6);
+        }
+        assert_eq!(segment_curve(0.25, 0.0), 0.25);
+        // a fast start covers more of the segment early on
+        assert!(segment_curve(0.25, 1.0) > 0.25);
+        assert!(segment_curve(0.25, -1.0) < 0.25);
+    }
+
+    fn linear_envelope() -> Envelope {
+        Envelope {
+            delay: 0.1,
+            attack: 0.2,
+            hold: 0.1,
+            decay: 0.4,
+            sustain: 0.5,
+            release: 1.0,
+            ..Envelope::default()
+        }
+    }
+
+    #[test]
+    fn envelope_runs_through_every_stage() {
+        let envelope = linear_envelope();
+        let level = |time| envelope_level(false, time, &envelope, 0.0, 1.0);
+        assert_eq!(level(0.05), 0.0);
+        assert!((level(0.2) - 0.5).abs() < 1e-5);
+        assert_eq!(level(0.35), 1.0);
+        assert!((level(0.6) - 0.75).abs() < 1e-5);
+        assert_eq!(level(2.0), 0.5);
+    }
+
+    #[test]
+    fn envelope_releases_from_the_given_level() {
+        let envelope = linear_envelope();
+        let level = |time| envelope_level(true, time, &envelope, 0.8, 1.0);
+        assert!((level(0.0) - 0.8).abs() < 1e-6);
+        assert!((level(0.5) - 0.4).abs() < 1e-5);
+        assert_eq!(level(1.0), 0.0);
+        assert_eq!(level(5.0), 0.0);
+    }
+
+    #[test]
+    fn release_scales_with_the_level_at_note_off() {
+        // what lets the partials without a decay scale share one envelope per note
+        let envelope = linear_envelope();
+        for time in [0.0, 0.3, 0.9, 1.5] {
+            let shared = envelope_level(true, time, &envelope, 1.0, 1.0);
+            assert_eq!(
+                0.6 * shared,
+                envelope_level(true, time, &envelope, 0.6, 1.0)
+            );
+        }
+    }
+
+    #[test]
+    fn decay_scale_stretches_decay_and_release() {
+        let envelope = linear_envelope();
+        assert!((envelope_level(false, 0.8, &envelope, 0.0, 0.5) - 0.5).abs() < 1e-6);
+        assert!((envelope_level(false, 0.8, &envelope, 0.0, 2.0) - 0.75).abs() < 1e-5);
+        assert!((envelope_level(true, 1.0, &envelope, 1.0, 2.0) - 0.5).abs() < 1e-5);
+    }
+
+    // three notes fill the polyphony, `prepare` sets them up before `note` comes in
+    fn stolen_by(policy: StealPolicy, note: u8, prepare: fn(&mut [Note])) -> Vec<u8> {
+        let mut synth = Furiri::default();
+        let mut patch = synth.patch;
+        patch.polyphony = 3;
+        patch.steal_policy = policy;
+        for key in [60, 62, 64] {
+            synth.note_on(key, 0.8, &patch);
+        }
+        prepare(&mut synth.current_notes);
+        synth.note_on(note, 0.8, &patch);
+        assert_eq!(synth.current_notes.len(), 4);
+        synth
+            .current_notes
+            .iter()
+            .filter(|n| n.stolen)
+            .map(|n| n.note)
+            .collect()
+    }
+
+    #[test]
+    fn steal_policies_pick_their_note() {
+        assert_eq!(stolen_by(StealPolicy::Oldest, 65, |_| {}), [60]);
+        let levels = |notes: &mut [Note]| {
+            for (note, level) in notes.iter_mut().zip([1.0, 0.2, 0.8]) {
+                note.levels[0] = level;
+            }
+        };
+        assert_eq!(stolen_by(StealPolicy::Quietest, 65, levels), [62]);
+        assert_eq!(
+            stolen_by(StealPolicy::Released, 65, |notes| notes[2].release()),
+            [64]
+        );
+        assert_eq!(stolen_by(StealPolicy::Released, 65, |_| {}), [60]);
+        assert_eq!(stolen_by(StealPolicy::SameNote, 62, |_| {}), [62]);
+        assert_eq!(stolen_by(StealPolicy::SameNote, 65, |_| {}), [60]);
+    }
+
+    #[test]
+    fn every_unison_copy_counts_as_a_voice() {
+        let mut synth = Furiri::default();
+        let mut patch = synth.patch;
+        patch.polyphony = 8;
+        // four copies from the mod wheel
+        patch.mod_slots[0] = ModSlot {
+            source: ModSource::ModWheel,
+            target: ModTarget::UnisonVoices,
+            number: 0,
+            depth: 3.0 / (MAX_UNISON - 1) as f32,
+        };
+        patch.mod_slot_count = 1;
+        synth.controls.mod_wheel = 1.0;
+        for key in [60, 62, 64] {
+            synth.note_on(key, 0.8, &patch);
+        }
+        assert!(synth.current_notes.iter().all(|n| n.unison_count == 4));
+        let stolen: Vec<u8> = synth
+            .current_notes
+            .iter()
+            .filter(|n| n.stolen)
+            .map(|n| n.note)
+            .collect();
+        assert_eq!(stolen, [60]);
+        // a stack bigger than the polyphony still plays, on its own
+        patch.polyphony = 2;
+        synth.note_on(65, 0.8, &patch);
+        assert_eq!(synth.current_notes.iter().filter(|n| !n.stolen).count(), 1);
+    }
+
+    #[test]
+    fn full_note_list_drops_the_most_faded_stolen_note() {
+        let mut synth = Furiri::default();
+        let patch = synth.patch;
+        for i in 0..MAX_NOTES {
+            synth.note_on((i % 128) as u8, 0.8, &patch);
+        }
+        assert_eq!(synth.current_notes.len(), MAX_NOTES);
+        assert_eq!(
+            synth.current_notes.iter().filter(|n| n.stolen).count(),
+            MAX_VOICES
+        );
+        let faded = &mut synth.current_notes[10];
+        faded.steal_fade = 0.1;
+        let started = faded.started;
+        synth.note_on(60, 0.8, &patch);
+        assert_eq!(synth.current_notes.len(), MAX_NOTES);
+        assert!(synth.current_notes.iter().all(|n| n.started != started));
+        assert_eq!(
+            synth.current_notes.iter().filter(|n| !n.stolen).count(),
+            MAX_VOICES
+        );
+    }
+
+    // what process does with a key in the mono voice modes
+    fn press(synth: &mut Furiri, note: u8, patch: &Patch) {
+        synth.held_notes.retain(|&n| n != note);
+        synth.held_notes.push(note);
+        synth.mono_note_on(note, 0.8, patch);
+    }
+
+    fn lift(synth: &mut Furiri, note: u8, patch: &Patch) -> bool {
+        synth.held_notes.retain(|&n| n != note);
+        synth.mono_note_off(note, patch)
+    }
+
+    #[test]
+    fn mono_keys_glide_on_a_single_note() {
+        let mut synth = Furiri::default();
+        let mut patch = synth.patch;
+        patch.glide_time = 0.1;
+        press(&mut synth, 60, &patch);
+        let ratio = synth.current_notes[0].ratio;
+        press(&mut synth, 64, &patch);
+        assert_eq!(synth.current_notes.len(), 1);
+        let note = &synth.current_notes[0];
+        assert_eq!(note.note, 64);
+        assert_eq!(note.glide, 0.0);
+        assert_eq!(note.glide_from, ratio);
+        // legato, the velocity stays with the first key
+        assert_eq!(note.velocity, (0.8 * 127.0) as u8);
+        // a key that doesn't have the priority is left out
+        synth.held_notes.insert(0, 67);
+        synth.mono_note_on(67, 0.8, &patch);
+        assert_eq!(synth.current_notes[0].note, 64);
+    }
+
+    #[test]
+    fn mono_note_off_goes_back_to_the_held_key() {
+        let mut synth = Furiri::default();
+        let patch = synth.patch;
+        press(&mut synth, 60, &patch);
+        press(&mut synth, 64, &patch);
+        press(&mut synth, 67, &patch);
+        // a key that isn't playing just leaves the list
+        assert!(lift(&mut synth, 64, &patch));
+        assert_eq!(synth.current_notes[0].note, 67);
+        assert!(lift(&mut synth, 67, &patch));
+        assert_eq!(synth.current_notes[0].note, 60);
+        // without glide the pitch is there at once
+        assert_eq!(synth.current_notes[0].glide, 1.0);
+        assert!(!lift(&mut synth, 60, &patch));
+        assert_eq!(synth.current_notes.len(), 1);
+    }
+
+    #[test]
+    fn retrigger_carries_every_envelope_on_from_its_level() {
+        let mut synth = Furiri::default();
+        let mut patch = synth.patch;
+        patch.envelope = linear_envelope();
+        patch.noise_envelope = Envelope {
+            delay: 0.0,
+            attack: 0.05,
+            ..linear_envelope()
+        };
+        patch.fm_envelope = Envelope {
+            delay: 0.02,
+            ..linear_envelope()
+        };
+        press(&mut synth, 60, &patch);
+        let note = &mut synth.current_notes[0];
+        note.levels[0] = 0.5;
+        note.noise_envelope = 0.25;
+        note.fm_envelope = 0.75;
+        synth.glide_to(0, 62, Some(1.0), &patch);
+        let note = &synth.current_notes[0];
+        assert_eq!((note.note, note.velocity), (62, 127));
+        let level = |time: f64, envelope| envelope_level(false, time as f32, envelope, 0.0, 1.0);
+        assert!((level(note.envelope_time, &patch.envelope) - 0.5).abs() < 1e-5);
+        assert!((level(note.noise_time, &patch.noise_envelope) - 0.25).abs() < 1e-5);
+        assert!((level(note.fm_time, &patch.fm_envelope) - 0.75).abs() < 1e-5);
+    }
+
+    #[test]
+    fn divisions_count_beats_and_off_keeps_the_time() {
+        assert_eq!(Division::Off.beats(), None);
+        assert_eq!(Division::Quarter.beats(), Some(1.0));
+        assert_eq!(Division::Bar.beats(), Some(4.0));
+    }
+
+    #[test]
+    fn envelope_times_round_trip_through_text() {
+        let param = envelope_time("Attack", 0.0);
+        for ms in [0.0, 12.5, 1234.5, 30000.0] {
+            let text = param.normalized_value_to_string(param.preview_normalized(ms), true);
+            let parsed = param.preview_plain(param.string_to_normalized_value(&text).unwrap());
+            assert!((parsed - ms).abs() < 0.05, "{text}");
+        }
+        let seconds = param.string_to_normalized_value("1.5 s").unwrap();
+        assert!((param.preview_plain(seconds) - 1500.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn segment_curve_inverse_undoes_the_curve() {
+        for curvature in [-1.0, -0.3, 0.0, 0.3, 1.0] {
+            for progress in [0.0, 0.1, 0.5, 0.9, 1.0] {
+                let fraction = segment_curve(progress, curvature);
+                let inverse = segment_curve_inverse(fraction, curvature);
+                assert!((inverse - progress).abs() < 1e-4, "{curvature} {progress}");
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::LfoShape;
+
+const TABLE_SIZE: usize = 4096; // power of two, linear interpolation error stays below -120 dB
+
+pub(crate) struct SineTable {
+    table: Vec<f32>, // one cycle plus a guard point for interpolation
+}
+
+impl SineTable {
+    pub(crate) fn new() -> Self {
+        Self {
+            table: (0..=TABLE_SIZE)
+                .map(|i| (std::f64::consts::TAU * i as f64 / TABLE_SIZE as f64).sin() as f32)
+                .collect(),
+        }
+    }
+
+    // phase in cycles, wraps around so any value is valid
+    #[inline]
+    pub(crate) fn sin(&self, phase: f32) -> f32 {
+        let pos = (phase - phase.floor()) * TABLE_SIZE as f32;
+        let index = pos as usize;
+        let frac = pos - index as f32;
+        let index = index & (TABLE_SIZE - 1);
+        let a = self.table[index];
+        let b = self.table[index + 1];
+        a + (b - a) * frac
+    }
+}
+
+impl Default for SineTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// xorshift32, good enough for panning, phases and noise
+pub(crate) struct Rng(u32);
+
+impl Rng {
+    pub(crate) fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    // uniform in [0, 1)
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    // uniform in [-1, 1)
+    pub(crate) fn next_bipolar(&mut self) -> f32 {
+        self.next_f32() * 2.0 - 1.0
+    }
+}
+
+// white noise through a band-pass state variable filter (Simper's TPT form)
+pub(crate) struct BandNoise {
+    rng: Rng,
+    ic1eq: f32,
+    ic2eq: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    k: f32,
+}
+
+impl BandNoise {
+    pub(crate) fn new(seed: u32) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            a3: 0.0,
+            k: 1.0,
+        }
+    }
+
+    // bandwidth in octaves
+    pub(crate) fn set_band(&mut self, center: f32, bandwidth: f32, sample_rate: f32) {
+        let center = center.clamp(10.0, sample_rate * 0.49);
+        let g = (std::f32::consts::PI * center / sample_rate).tan();
+        let width = 2.0f32.powf(bandwidth);
+        self.k = (width - 1.0) / width.sqrt(); // 1 / Q
+        self.a1 = 1.0 / (1.0 + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        let v0 = self.rng.next_bipolar();
+        let v3 = v0 - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+        self.k * v1 // unity gain at the center frequency
+    }
+}
+
+// low frequency oscillator with a bipolar output, the random shapes draw a new value every cycle
+pub(crate) struct Lfo {
+    phase: f32,
+    rng: Rng,
+    held: f32,
+    target: f32, // where the smooth random shape is heading
+}
+
+impl Lfo {
+    pub(crate) fn new(seed: u32) -> Self {
+        let mut rng = Rng::new(seed);
+        let held = rng.next_bipolar();
+        let target = rng.next_bipolar();
+        Self {
+            phase: 0.0,
+            rng,
+            held,
+            target,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
+    // increment and offset in cycles, the periodic shapes start at zero and rise like the sine
+    pub(crate) fn next(&mut self, shape: LfoShape, increment: f32, offset: f32) -> f32 {
+        let position = (self.phase + offset).fract();
+        let value = match shape {
+            LfoShape::Sine => (std::f32::consts::TAU * position).sin(),
+            LfoShape::Triangle => 1.0 - 4.0 * ((position + 0.25).fract() - 0.5).abs(),
+            LfoShape::Saw => 2.0 * (position + 0.5).fract() - 1.0,
+            LfoShape::Square => {
+                if position < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            LfoShape::SampleHold => self.held,
+            LfoShape::SmoothRandom => {
+                let t = 0.5 - 0.5 * (std::f32::consts::PI * position).cos();
+                self.held + (self.target - self.held) * t
+            }
+        };
+        // only the increment can finish a cycle, a moving offset doesn't draw new random values
+        if position + increment >= 1.0 {
+            self.held = self.target;
+            self.target = self.rng.next_bipolar();
+        }
+        self.phase = (self.phase + increment).fract();
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // one value at `position` cycles into the shape
+    fn value_at(shape: LfoShape, position: f32) -> f32 {
+        Lfo::new(1).next(shape, 0.0, position)
+    }
+
+    #[test]
+    fn periodic_shapes_start_at_zero_and_rise() {
+        let quarters = [
+            (LfoShape::Sine, [0.0, 1.0, 0.0, -1.0]),
+            (LfoShape::Triangle, [0.0, 1.0, 0.0, -1.0]),
+            (LfoShape::Saw, [0.0, 0.5, -1.0, -0.5]),
+            (LfoShape::Square, [1.0, 1.0, -1.0, -1.0]),
+        ];
+        for (shape, values) in quarters {
+            for (quarter, expected) in values.into_iter().enumerate() {
+                let value = value_at(shape, quarter as f32 / 4.0);
+                assert!((value - expected).abs() < 1e-6, "{quarter}/4: {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn sample_and_hold_draws_once_per_cycle() {
+        let mut lfo = Lfo::new(1);
+        let first = lfo.next(LfoShape::SampleHold, 0.25, 0.0);
+        for _ in 0..3 {
+            assert_eq!(lfo.next(LfoShape::SampleHold, 0.25, 0.0), first);
+        }
+        assert_ne!(lfo.next(LfoShape::SampleHold, 0.25, 0.0), first);
+    }
+
+    #[test]
+    fn moving_the_offset_holds_the_value() {
+        let mut lfo = Lfo::new(1);
+        let first = lfo.next(LfoShape::SampleHold, 0.0, 0.9);
+        for offset in [0.1, 0.95, 0.0, 0.5] {
+            assert_eq!(lfo.next(LfoShape::SampleHold, 0.0, offset), first);
+        }
+    }
+
+    #[test]
+    fn smooth_random_arrives_where_the_next_cycle_starts() {
+        let mut lfo = Lfo::new(1);
+        for _ in 0..3 {
+            lfo.next(LfoShape::SmoothRandom, 0.25, 0.0);
+        }
+        let target = lfo.target;
+        lfo.next(LfoShape::SmoothRandom, 0.25, 0.0);
+        assert_eq!(lfo.next(LfoShape::SmoothRandom, 0.25, 0.0), target);
+    }
+}
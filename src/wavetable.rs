@@ -0,0 +1,163 @@
+use std::f32::consts::TAU;
+use std::path::Path;
+
+use crate::{FuririParams, Partial};
+
+pub(crate) const CYCLE_LENGTH: usize = 2048;
+pub(crate) const FRAME_COUNT: usize = 64;
+const MIN_TILT: f32 = -12.0; // dB/oct, the full range of the tilt macro
+const MAX_TILT: f32 = 12.0;
+
+// one cycle at the current settings
+pub(crate) fn single_cycle(params: &FuririParams) -> Vec<Vec<f32>> {
+    let morph = params.morph.amount.value();
+    vec![render_cycle(params.partials().map(|p| p.morphed(morph)))]
+}
+
+// frames sweeping the tilt macro from dark to bright, at the current morph position
+pub(crate) fn tilt_sweep(params: &FuririParams) -> Vec<Vec<f32>> {
+    let morph = params.morph.amount.value();
+    (0..FRAME_COUNT)
+        .map(|frame| {
+            let tilt = MIN_TILT + (MAX_TILT - MIN_TILT) * frame as f32 / (FRAME_COUNT - 1) as f32;
+            render_cycle(params.partials_with_tilt(tilt).map(|p| p.morphed(morph)))
+        })
+        .collect()
+}
+
+// frames sweeping the morph from spectrum a to b
+pub(crate) fn morph_sweep(params: &FuririParams) -> Vec<Vec<f32>> {
+    (0..FRAME_COUNT)
+        .map(|frame| {
+            let morph = frame as f32 / (FRAME_COUNT - 1) as f32;
+            render_cycle(params.partials().map(|p| p.morphed(morph)))
+        })
+        .collect()
+}
+
+// ratios are rounded to whole harmonics so the cycle loops without a click, undertones can't
+// fit in a single cycle and are left out
+fn render_cycle(partials: impl Iterator<Item = Partial>) -> Vec<f32> {
+    let mut cycle = vec![0.0; CYCLE_LENGTH];
+    for partial in partials {
+        // checked before rounding, the 1/2 undertone would round up to the fundamental
+        if partial.ratio < 1.0 {
+            continue;
+        }
+        let harmonic = partial.ratio.round();
+        if harmonic >= (CYCLE_LENGTH / 2) as f32 {
+            continue;
+        }
+        for (i, sample) in cycle.iter_mut().enumerate() {
+            let p = i as f32 / CYCLE_LENGTH as f32;
+            *sample += partial.amplitude * (TAU * (harmonic * p + partial.phase)).sin();
+        }
+    }
+    cycle
+}
+
+// frames back to back as 32 bit float, the layout most wavetable synths read, normalized
+// together so the level differences between frames survive
+pub(crate) fn write_wav(path: &Path, frames: &[Vec<f32>]) -> Result<(), hound::Error> {
+    let peak = frames
+        .iter()
+        .flatten()
+        .fold(0.0f32, |max, sample| max.max(sample.abs()));
+    let scale = if peak > 0.0 { 1.0 / peak } else { 1.0 };
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate: 44100,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    for sample in frames.iter().flatten() {
+        writer.write_sample(sample * scale)?;
+    }
+    writer.finalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis;
+
+    fn partial(ratio: f32, amplitude: f32, phase: f32) -> Partial {
+        Partial {
+            amplitude,
+            phase,
+            ratio,
+            ..Partial::default()
+        }
+    }
+
+    // writes the frames and reads the spectrum of each one back
+    fn round_trip(name: &str, frames: &[Vec<f32>]) -> Vec<Vec<analysis::Harmonic>> {
+        let path = std::env::temp_dir().join(format!("furiri-{name}-{}.wav", std::process::id()));
+        write_wav(&path, frames).unwrap();
+        let (samples, _) = analysis::read_wav(&path, usize::MAX).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), frames.len() * CYCLE_LENGTH);
+        samples
+            .chunks(CYCLE_LENGTH)
+            .map(|cycle| analysis::cycle_harmonics(cycle, 4))
+            .collect()
+    }
+
+    #[test]
+    fn exported_cycle_imports_as_the_same_spectrum() {
+        let cycle = render_cycle([partial(1.0, 1.0, 0.0), partial(3.0, 0.5, 0.25)].into_iter());
+        let harmonics = &round_trip("cycle", &[cycle])[0];
+        // the export is normalized, so compare against the fundamental
+        let fundamental = harmonics[0].amplitude;
+        assert!((harmonics[2].amplitude / fundamental - 0.5).abs() < 1e-3);
+        assert!((harmonics[2].phase - 0.25).abs() < 1e-3);
+        assert!(harmonics[1].amplitude < 1e-3);
+        assert!(harmonics[3].amplitude < 1e-3);
+    }
+
+    #[test]
+    fn long_files_are_read_from_the_middle() {
+        let frames = [0.25, 1.0, 0.5]
+            .map(|amplitude| render_cycle([partial(2.0, amplitude, 0.0)].into_iter()));
+        let path = std::env::temp_dir().join(format!("furiri-middle-{}.wav", std::process::id()));
+        write_wav(&path, &frames).unwrap();
+        let (samples, _) = analysis::read_wav(&path, CYCLE_LENGTH).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(samples.len(), CYCLE_LENGTH);
+        // the loudest frame sets the level, so the middle one comes back as it was
+        let read = analysis::cycle_harmonics(&samples, 2)[1].amplitude;
+        let written = analysis::cycle_harmonics(&frames[1], 2)[1].amplitude;
+        assert!((read - written).abs() < 1e-3, "{read} {written}");
+    }
+
+    #[test]
+    fn frames_keep_their_level_differences() {
+        let frames =
+            [1.0, 0.5].map(|amplitude| render_cycle([partial(2.0, amplitude, 0.0)].into_iter()));
+        let spectra = round_trip("frames", &frames);
+        assert!((spectra[1][1].amplitude / spectra[0][1].amplitude - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn undertones_are_left_out() {
+        let cycle =
+            render_cycle([partial(0.5, 1.0, 0.0), partial(1.0 / 3.0, 1.0, 0.0)].into_iter());
+        assert!(cycle.iter().all(|&sample| sample == 0.0));
+        // an undertone rounded into the fundamental would halve the third against it
+        let partials = [
+            partial(1.0, 1.0, 0.0),
+            partial(3.0, 0.5, 0.0),
+            partial(0.5, 1.0, 0.0),
+        ];
+        let harmonics = &round_trip("undertone", &[render_cycle(partials.into_iter())])[0];
+        assert!((harmonics[2].amplitude / harmonics[0].amplitude - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn inharmonic_ratios_are_rounded() {
+        let cycle = render_cycle([partial(2.2, 1.0, 0.0)].into_iter());
+        let harmonics = analysis::cycle_harmonics(&cycle, 4);
+        assert!((harmonics[1].amplitude - 1.0).abs() < 1e-3);
+    }
+}